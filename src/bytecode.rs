@@ -0,0 +1,164 @@
+use crate::error::DecodeError;
+use crate::opp::Opp;
+use crate::token::{
+    Token,
+    intern,
+};
+
+/// Starts every encoded program, so it can be told apart from program text
+pub const MAGIC : &[u8] = b"\0slug";
+
+/// Marks a literal, followed by its value as a zigzag varint
+const LITERAL : u8 = 0xFF;
+/// Marks a custom opperation, followed by the length of its name as a varint
+/// and then the name
+const CUSTOM : u8 = 0xFE;
+
+/// Encodes tokens compactly. Each opperation is the single byte of its
+/// discriminant and each literal is a marker byte followed by a varint, so
+/// small values take two bytes.
+#[must_use]
+pub fn to_bytes(tokens : &[Token]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    for token in tokens {
+        match token {
+            Token::Opp(op) => out.push(*op as u8),
+            Token::Value(v) => {
+                out.push(LITERAL);
+                // Zigzag encode so small negative values stay small
+                #[expect(
+                    clippy::cast_sign_loss,
+                    reason = "Reinterpreting the bits is the point of zigzag encoding"
+                )]
+                let zigzag = ((v << 1) ^ (v >> 63)) as u64;
+                write_varint(&mut out, zigzag);
+            },
+            Token::Custom(name) => {
+                out.push(CUSTOM);
+                write_varint(&mut out, name.len() as u64);
+                out.extend_from_slice(name.as_bytes());
+            },
+        }
+    }
+    out
+}
+
+/// Decodes tokens encoded by `to_bytes`.
+///
+/// The names of custom opperations are leaked, like with `Slug::register_op`,
+/// since tokens refer to them for the life of the program. Each distinct name
+/// is only leaked once, however many times it's decoded.
+///
+/// # Errors
+/// Errors with the byte offset of the problem if the data isn't a valid
+/// encoding
+pub fn from_bytes(data : &[u8]) -> Result<Vec<Token>, DecodeError> {
+    if !data.starts_with(MAGIC) {
+        return Err(DecodeError::MissingMagic);
+    }
+    let mut idx = MAGIC.len();
+
+    let mut tokens = Vec::new();
+    while let Some(&byte) = data.get(idx) {
+        let start = idx;
+        idx += 1;
+
+        let token = match byte {
+            LITERAL => {
+                let zigzag = read_varint(data, &mut idx)?;
+                #[expect(
+                    clippy::cast_possible_wrap,
+                    reason = "Reinterpreting the bits is the point of zigzag encoding"
+                )]
+                let v = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+                Token::Value(v)
+            },
+            CUSTOM => {
+                let len = usize::try_from(read_varint(data, &mut idx)?)
+                    .map_err(|_| DecodeError::InvalidVarint(start))?;
+                let end = idx.checked_add(len).ok_or(DecodeError::Truncated(start))?;
+                let name = data.get(idx .. end).ok_or(DecodeError::Truncated(start))?;
+                let name = str::from_utf8(name).map_err(|_| DecodeError::InvalidName(start))?;
+                idx = end;
+                Token::Custom(intern(name))
+            },
+            op => {
+                Opp::all()
+                    .get(usize::from(op))
+                    .map(|op| Token::Opp(*op))
+                    .ok_or(DecodeError::UnknownOpcode(start))?
+            },
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Writes a value seven bits at a time, low bits first, with the top bit of
+/// each byte set when more follow
+fn write_varint(out : &mut Vec<u8>, mut v : u64) {
+    while v >= 0x80 {
+        out.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "The loop leaves less than 0x80"
+    )]
+    out.push(v as u8);
+}
+
+/// Reads a value written by `write_varint`, moving `idx` past it
+fn read_varint(data : &[u8], idx : &mut usize) -> Result<u64, DecodeError> {
+    let start = *idx;
+    let mut v = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*idx).ok_or(DecodeError::Truncated(start))?;
+        *idx += 1;
+
+        if shift >= 64 {
+            return Err(DecodeError::InvalidVarint(start));
+        }
+        v |= u64::from(byte & 0x7F) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let tokens = vec![
+            Token::Value(0),
+            Token::Value(-1),
+            Token::Value(i64::MIN),
+            Token::Value(i64::MAX),
+            Token::Opp(Opp::Add),
+            Token::Custom("double"),
+        ];
+        let bytes = to_bytes(&tokens);
+        assert_eq!(from_bytes(&bytes), Ok(tokens));
+    }
+
+    #[test]
+    fn names_are_interned() {
+        let bytes = to_bytes(&[Token::Custom("double"), Token::Custom("double")]);
+        let tokens = from_bytes(&bytes).expect("Encoded tokens should decode");
+        let again = from_bytes(&bytes).expect("Encoded tokens should decode");
+        let [Token::Custom(a), Token::Custom(b)] = tokens[..] else {
+            panic!("Expected two custom tokens, got {tokens:?}");
+        };
+        let [Token::Custom(c), _] = again[..] else {
+            panic!("Expected two custom tokens, got {again:?}");
+        };
+        assert!(a.as_ptr() == b.as_ptr() && b.as_ptr() == c.as_ptr());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::error::CompileError;
+use crate::opp::Opp;
+use crate::token::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InfixOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// Unary minus
+    Neg,
+    LParen,
+}
+
+impl InfixOp {
+    const fn precedence(self) -> u8 {
+        match self {
+            Self::LParen => 0,
+            Self::Add | Self::Sub => 1,
+            Self::Mul | Self::Div => 2,
+            Self::Neg => 3,
+        }
+    }
+
+    fn emit(self, out : &mut Vec<Token>) {
+        match self {
+            Self::Add => out.push(Token::Opp(Opp::Add)),
+            Self::Sub => out.push(Token::Opp(Opp::Sub)),
+            Self::Mul => out.push(Token::Opp(Opp::Mul)),
+            // `div` leaves the remainder under the quotient, so get rid of it
+            Self::Div => {
+                out.extend([
+                    Token::Opp(Opp::Div),
+                    Token::Opp(Opp::Swap),
+                    Token::Opp(Opp::Drop),
+                ]);
+            },
+            Self::Neg => out.extend([Token::Value(-1), Token::Opp(Opp::Mul)]),
+            Self::LParen => unreachable!("Parentheses are never emitted"),
+        }
+    }
+}
+
+/// Compiles an infix arithmetic expression such as `(2 + 3) * 4` into the
+/// postfix tokens that evaluate it.
+///
+/// Supports integer literals, `+`, `-`, `*`, `/`, unary minus, and
+/// parentheses. Division truncates toward zero like `div`. The output assumes
+/// the runtime isn't reversing operands.
+///
+/// # Errors
+/// Errors with the byte offset of the problem if the expression is malformed
+pub fn compile_infix(expr : &str) -> Result<Vec<Token>, CompileError> {
+    let mut out = Vec::new();
+    let mut ops : Vec<(InfixOp, usize)> = Vec::new();
+    // Whether the next item must be a value, a prefix operator, or an opening
+    // parenthesis
+    let mut expect_operand = true;
+
+    let mut chars = expr.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {},
+            '0' ..= '9' => {
+                if !expect_operand {
+                    return Err(CompileError::UnexpectedChar(idx));
+                }
+
+                let mut end = idx + c.len_utf8();
+                while let Some((i, d)) = chars.next_if(|(_, d)| d.is_ascii_digit()) {
+                    end = i + d.len_utf8();
+                }
+
+                let value = expr[idx .. end]
+                    .parse()
+                    .map_err(|_| CompileError::InvalidLiteral(idx))?;
+                out.push(Token::Value(value));
+                expect_operand = false;
+            },
+            '(' => {
+                if !expect_operand {
+                    return Err(CompileError::UnexpectedChar(idx));
+                }
+                ops.push((InfixOp::LParen, idx));
+            },
+            ')' => {
+                if expect_operand {
+                    return Err(CompileError::MissingOperand(idx));
+                }
+
+                loop {
+                    match ops.pop() {
+                        Some((InfixOp::LParen, _)) => break,
+                        Some((op, _)) => op.emit(&mut out),
+                        None => return Err(CompileError::UnbalancedParen(idx)),
+                    }
+                }
+            },
+            '-' if expect_operand => ops.push((InfixOp::Neg, idx)),
+            '+' | '-' | '*' | '/' => {
+                if expect_operand {
+                    return Err(CompileError::MissingOperand(idx));
+                }
+
+                let op = match c {
+                    '+' => InfixOp::Add,
+                    '-' => InfixOp::Sub,
+                    '*' => InfixOp::Mul,
+                    _ => InfixOp::Div,
+                };
+
+                // Every binary operator is left associative
+                while let Some((top, _)) = ops.last()
+                    && top.precedence() >= op.precedence()
+                {
+                    top.emit(&mut out);
+                    ops.pop();
+                }
+
+                ops.push((op, idx));
+                expect_operand = true;
+            },
+            _ => return Err(CompileError::UnexpectedChar(idx)),
+        }
+    }
+
+    if expect_operand {
+        return Err(CompileError::MissingOperand(expr.len()));
+    }
+
+    while let Some((op, idx)) = ops.pop() {
+        if op == InfixOp::LParen {
+            return Err(CompileError::UnbalancedParen(idx));
+        }
+        op.emit(&mut out);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Slug;
+
+    /// Compiles an expression and runs the result
+    fn eval(expr : &str) -> i64 {
+        let mut runtime = Slug {
+            tokens : compile_infix(expr).expect("The expression should compile"),
+            eof : true,
+            ..Slug::new()
+        };
+        runtime
+            .execute()
+            .expect("The compiled program should run")
+            .expect("The compiled program should return a value")
+    }
+
+    #[test]
+    fn compiled_expressions_evaluate() {
+        assert_eq!(eval("(2 + 3) * 4"), 20);
+        assert_eq!(eval("2 + 3 * 4"), 14);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("17 / 5"), 3);
+        assert_eq!(eval("-(2 + 3)"), -5);
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert_eq!(
+            compile_infix("2 +").err(),
+            Some(CompileError::MissingOperand(3))
+        );
+        assert_eq!(
+            compile_infix("(2 + 3").err(),
+            Some(CompileError::UnbalancedParen(0))
+        );
+        assert_eq!(
+            compile_infix("2 + 3)").err(),
+            Some(CompileError::UnbalancedParen(5))
+        );
+        assert_eq!(
+            compile_infix("2 $ 3").err(),
+            Some(CompileError::UnexpectedChar(2))
+        );
+        assert_eq!(
+            compile_infix("99999999999999999999").err(),
+            Some(CompileError::InvalidLiteral(0))
+        );
+    }
+}
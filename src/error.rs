@@ -9,12 +9,20 @@ use std::io;
 #[derive(Debug)]
 pub enum ExecutionError {
     IoError(io::Error),
-    ParseTextError(ParseTextError),
+    /// Every problem found parsing the program, in order
+    ParseTextError(Vec<ParseTextError>),
     RuntimeError(RuntimeError),
+    DecodeError(DecodeError),
 }
 
 impl From<ParseTextError> for ExecutionError {
     fn from(v : ParseTextError) -> Self {
+        Self::ParseTextError(vec![v])
+    }
+}
+
+impl From<Vec<ParseTextError>> for ExecutionError {
+    fn from(v : Vec<ParseTextError>) -> Self {
         Self::ParseTextError(v)
     }
 }
@@ -25,17 +33,127 @@ impl From<RuntimeError> for ExecutionError {
     }
 }
 
+impl From<DecodeError> for ExecutionError {
+    fn from(v : DecodeError) -> Self {
+        Self::DecodeError(v)
+    }
+}
+
 impl From<io::Error> for ExecutionError {
     fn from(v : io::Error) -> Self {
         Self::IoError(v)
     }
 }
 
+impl Display for ExecutionError {
+    fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IoError(err) => write!(f, "{err}"),
+            Self::ParseTextError(errs) => {
+                let lines : Vec<String> = errs.iter().map(ToString::to_string).collect();
+                write!(f, "{}", lines.join("\n"))
+            },
+            Self::RuntimeError(err) => write!(f, "{err}"),
+            Self::DecodeError(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ExecutionError {}
+
 #[derive(Debug)]
 pub struct ParseTextError {
-    pub idx : usize,
+    /// Index of the offending word
+    pub idx :  usize,
+    pub kind : ParseTextErrorKind,
+}
+
+impl Display for ParseTextError {
+    fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
+        let idx = self.idx;
+        match self.kind {
+            ParseTextErrorKind::UnknownToken => write!(f, "Unknown token at word {idx}"),
+            ParseTextErrorKind::LiteralOutOfRange => {
+                write!(f, "Integer literal out of range at word {idx}")
+            },
+            ParseTextErrorKind::InvalidData => {
+                write!(f, "Data section value isn't an integer at word {idx}")
+            },
+            ParseTextErrorKind::UnterminatedData => {
+                write!(
+                    f,
+                    "Data section starting at word {idx} is missing its `end`"
+                )
+            },
+        }
+    }
+}
+
+impl Error for ParseTextError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseTextErrorKind {
+    UnknownToken,
+    /// An integer literal doesn't fit in 64 bits
+    LiteralOutOfRange,
+    /// A value in a data section isn't an integer
+    InvalidData,
+    /// A data section is missing its `end`
+    UnterminatedData,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// Positions are byte offsets into the encoded data
+pub enum DecodeError {
+    /// The data doesn't start with `bytecode::MAGIC`
+    MissingMagic,
+    UnknownOpcode(usize),
+    /// The data ends part way through a token
+    Truncated(usize),
+    /// A varint is too long to fit in 64 bits
+    InvalidVarint(usize),
+    /// A custom opperation's name isn't UTF-8
+    InvalidName(usize),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingMagic => write!(f, "Not an assembled Slug program"),
+            Self::UnknownOpcode(idx) => write!(f, "Unknown opcode at byte {idx}"),
+            Self::Truncated(idx) => write!(f, "Data ends part way through the token at byte {idx}"),
+            Self::InvalidVarint(idx) => write!(f, "Value too large at byte {idx}"),
+            Self::InvalidName(idx) => write!(f, "Opperation name isn't UTF-8 at byte {idx}"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+#[derive(Debug, PartialEq, Eq)]
+// Positions are byte offsets into the expression
+pub enum CompileError {
+    UnexpectedChar(usize),
+    InvalidLiteral(usize),
+    MissingOperand(usize),
+    UnbalancedParen(usize),
 }
 
+impl Display for CompileError {
+    fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedChar(i) => write!(f, "Unexpected character at offset {i}"),
+            Self::InvalidLiteral(i) => {
+                write!(f, "Integer literal at offset {i} doesn't fit in 64 bits")
+            },
+            Self::MissingOperand(i) => write!(f, "Expected a value at offset {i}"),
+            Self::UnbalancedParen(i) => write!(f, "Unbalanced parenthesis at offset {i}"),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
 #[derive(Debug, PartialEq, Eq)]
 // Token values are 0 indexed
 pub enum RuntimeError {
@@ -44,15 +162,34 @@ pub enum RuntimeError {
     AfterProgramRead,
     TokenLimitHit(i64),
     StackLimitHit(i64),
+    TimeLimitHit(i64),
     NoOut,
     NoTokens,
+    WriteFailed(i64),
+    AssertionFailed(i64),
+    IndexOutOfBounds(i64),
+    Overflow(i64),
+    NegativeOperand(i64),
+    InvalidRange(i64),
+    UnknownCustomOp(i64),
+    DivByZero(i64),
+    JumpTooFar(i64),
+    ReadFailed(i64),
+    ProtectedUnderflow(i64),
+    LiteralTooLarge(i64),
+    NonPositiveOperand(i64),
+    MemoryLimitHit(i64),
+    InputError(i64),
+    AlreadyHalted,
+    BadChar(i64),
+    NonDeterministic(i64),
 }
 
 impl Display for RuntimeError {
     fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
         let e = match self {
             Self::UnderRead(t) => {
-                format!("Attempted to read from the stack when it is empty, occured at token {t}",)
+                format!("Attempted to read from the stack when it is empty, occured at token {t}")
             },
             Self::BreforeProgramRead => {
                 "Moved the execution pointer before the start of the program".to_owned()
@@ -61,16 +198,383 @@ impl Display for RuntimeError {
                 "Moved the execution pointer past the end of the program".to_owned()
             },
             Self::TokenLimitHit(t) => {
-                format!("Exceeded the given token limit, occured at token {t}",)
+                format!("Exceeded the given token limit, occured at token {t}")
             },
             Self::StackLimitHit(t) => {
-                format!("Exceeded the given stack size limit, occured at token {t}",)
+                format!("Exceeded the given stack size limit, occured at token {t}")
+            },
+            Self::TimeLimitHit(t) => {
+                format!("Exceeded the given time limit, occured at token {t}")
             },
             Self::NoOut => "Exited without a value on the stack to return".to_owned(),
             Self::NoTokens => "There are no tokens in the input".to_owned(),
+            Self::WriteFailed(t) => format!("Failed to write output, occured at token {t}"),
+            Self::AssertionFailed(t) => format!("Assertion failed, occured at token {t}"),
+            Self::IndexOutOfBounds(t) => format!("Index out of bounds, occured at token {t}"),
+            Self::Overflow(t) => format!("Arithmetic overflow occured at token {t}"),
+            Self::NegativeOperand(t) => {
+                format!("Expected a non-negative value on the stack, occured at token {t}")
+            },
+            Self::InvalidRange(t) => {
+                format!("The minimum of a range is above its maximum, occured at token {t}")
+            },
+            Self::UnknownCustomOp(t) => {
+                format!("Executed a custom opperation that isn't registered, occured at token {t}")
+            },
+            Self::DivByZero(t) => format!("Attempted to divide by zero, occured at token {t}"),
+            Self::ReadFailed(t) => format!("Failed to read input, occured at token {t}"),
+            Self::ProtectedUnderflow(t) => {
+                format!("Attempted to pop a protected value off the stack, occured at token {t}")
+            },
+            Self::NonPositiveOperand(t) => {
+                format!("Expected a positive value on the stack, occured at token {t}")
+            },
+            Self::LiteralTooLarge(t) => {
+                format!("A literal is larger than the maximum allowed, occured at token {t}")
+            },
+            Self::JumpTooFar(t) => {
+                format!("Jumped further than the maximum jump distance, occured at token {t}")
+            },
+            Self::InputError(t) => {
+                format!("Expected an integer in the input, occured at token {t}")
+            },
+            Self::AlreadyHalted => "Executed a program that has already exited".to_owned(),
+            Self::BadChar(t) => {
+                format!("Emitted a value that isn't a Unicode character, occured at token {t}")
+            },
+            Self::NonDeterministic(t) => {
+                format!(
+                    "Ran an opperation whose result differs between runs in a sandbox, occured \
+                     at token {t}"
+                )
+            },
+            Self::MemoryLimitHit(t) => {
+                format!(
+                    "Exceeded the given limit on values held by the stack and memory, occured \
+                     at token {t}"
+                )
+            },
         };
         write!(f, "{e}")
     }
 }
 
 impl Error for RuntimeError {}
+
+impl RuntimeError {
+    /// Every kind of runtime error, with placeholder token positions, for
+    /// looking an error up by its name
+    #[must_use]
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::UnderRead(0),
+            Self::BreforeProgramRead,
+            Self::AfterProgramRead,
+            Self::TokenLimitHit(0),
+            Self::StackLimitHit(0),
+            Self::TimeLimitHit(0),
+            Self::NoOut,
+            Self::NoTokens,
+            Self::WriteFailed(0),
+            Self::AssertionFailed(0),
+            Self::IndexOutOfBounds(0),
+            Self::Overflow(0),
+            Self::NegativeOperand(0),
+            Self::InvalidRange(0),
+            Self::UnknownCustomOp(0),
+            Self::DivByZero(0),
+            Self::JumpTooFar(0),
+            Self::ReadFailed(0),
+            Self::ProtectedUnderflow(0),
+            Self::LiteralTooLarge(0),
+            Self::NonPositiveOperand(0),
+            Self::MemoryLimitHit(0),
+            Self::InputError(0),
+            Self::AlreadyHalted,
+            Self::BadChar(0),
+            Self::NonDeterministic(0),
+        ]
+    }
+
+    /// The name of the kind of error, as taken by `explain`
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::UnderRead(_) => "underread",
+            Self::BreforeProgramRead => "beforeprogramread",
+            Self::AfterProgramRead => "afterprogramread",
+            Self::TokenLimitHit(_) => "tokenlimithit",
+            Self::StackLimitHit(_) => "stacklimithit",
+            Self::TimeLimitHit(_) => "timelimithit",
+            Self::NoOut => "noout",
+            Self::NoTokens => "notokens",
+            Self::WriteFailed(_) => "writefailed",
+            Self::AssertionFailed(_) => "assertionfailed",
+            Self::IndexOutOfBounds(_) => "indexoutofbounds",
+            Self::Overflow(_) => "overflow",
+            Self::NegativeOperand(_) => "negativeoperand",
+            Self::InvalidRange(_) => "invalidrange",
+            Self::UnknownCustomOp(_) => "unknowncustomop",
+            Self::DivByZero(_) => "divbyzero",
+            Self::JumpTooFar(_) => "jumptoofar",
+            Self::ReadFailed(_) => "readfailed",
+            Self::ProtectedUnderflow(_) => "protectedunderflow",
+            Self::LiteralTooLarge(_) => "literaltoolarge",
+            Self::NonPositiveOperand(_) => "nonpositiveoperand",
+            Self::MemoryLimitHit(_) => "memorylimithit",
+            Self::InputError(_) => "inputerror",
+            Self::AlreadyHalted => "alreadyhalted",
+            Self::BadChar(_) => "badchar",
+            Self::NonDeterministic(_) => "nondeterministic",
+        }
+    }
+
+    /// The index of the token that caused the error, if it was caused by one
+    #[must_use]
+    pub const fn token_index(&self) -> Option<i64> {
+        match self {
+            Self::UnderRead(t)
+            | Self::TokenLimitHit(t)
+            | Self::StackLimitHit(t)
+            | Self::TimeLimitHit(t)
+            | Self::WriteFailed(t)
+            | Self::AssertionFailed(t)
+            | Self::IndexOutOfBounds(t)
+            | Self::Overflow(t)
+            | Self::NegativeOperand(t)
+            | Self::InvalidRange(t)
+            | Self::UnknownCustomOp(t)
+            | Self::DivByZero(t)
+            | Self::JumpTooFar(t)
+            | Self::ReadFailed(t)
+            | Self::ProtectedUnderflow(t)
+            | Self::LiteralTooLarge(t)
+            | Self::NonPositiveOperand(t)
+            | Self::MemoryLimitHit(t)
+            | Self::InputError(t)
+            | Self::BadChar(t)
+            | Self::NonDeterministic(t) => Some(*t),
+            Self::BreforeProgramRead
+            | Self::AfterProgramRead
+            | Self::NoOut
+            | Self::NoTokens
+            | Self::AlreadyHalted => None,
+        }
+    }
+
+    /// Moves the error to the token at `index`, leaving errors that aren't
+    /// tied to a token unchanged
+    #[must_use]
+    pub const fn at(mut self, index : i64) -> Self {
+        match &mut self {
+            Self::UnderRead(t)
+            | Self::TokenLimitHit(t)
+            | Self::StackLimitHit(t)
+            | Self::TimeLimitHit(t)
+            | Self::WriteFailed(t)
+            | Self::AssertionFailed(t)
+            | Self::IndexOutOfBounds(t)
+            | Self::Overflow(t)
+            | Self::NegativeOperand(t)
+            | Self::InvalidRange(t)
+            | Self::UnknownCustomOp(t)
+            | Self::DivByZero(t)
+            | Self::JumpTooFar(t)
+            | Self::ReadFailed(t)
+            | Self::ProtectedUnderflow(t)
+            | Self::LiteralTooLarge(t)
+            | Self::NonPositiveOperand(t)
+            | Self::MemoryLimitHit(t)
+            | Self::InputError(t)
+            | Self::BadChar(t)
+            | Self::NonDeterministic(t) => *t = index,
+            Self::BreforeProgramRead
+            | Self::AfterProgramRead
+            | Self::NoOut
+            | Self::NoTokens
+            | Self::AlreadyHalted => {},
+        }
+        self
+    }
+
+    /// Finds the kind of error with the given name, ignoring case
+    #[must_use]
+    pub fn from_name(name : &str) -> Option<&'static Self> {
+        Self::all()
+            .iter()
+            .find(|err| err.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Describes the kind of error in depth, with a suggested fix and a small
+    /// example that causes it
+    #[must_use]
+    #[expect(clippy::too_many_lines, reason = "Boo Hoo Clippy")]
+    pub const fn explain(&self) -> &'static str {
+        match self {
+            Self::UnderRead(_) => {
+                "An opperation tried to pop a value when the stack was empty, a stack \
+                 underflow.\n\nCheck that enough values are pushed before the \
+                 opperation.\n\nExample: `1 add` only has one value for `add`, which needs two."
+            },
+            Self::BreforeProgramRead => {
+                "A jump moved the execution pointer before the first token.\n\nCheck the \
+                 targets of `goto` and the offsets of `hop` and `hopif`.\n\nExample: `-5 \
+                 hop` jumps back five tokens from the second token."
+            },
+            Self::AfterProgramRead => {
+                "A jump moved the execution pointer past the last token.\n\nCheck the \
+                 targets of `goto` and the offsets of `hop` and `hopif`. Tokens are counted \
+                 from 0.\n\nExample: `10 goto` in a two token program."
+            },
+            Self::TokenLimitHit(_) => {
+                "The program executed more tokens than the token limit allows, usually \
+                 because of a loop that never ends.\n\nCheck the loop's exit condition, or \
+                 raise the limit with `--token-limit`.\n\nExample: `0 goto` loops \
+                 forever."
+            },
+            Self::StackLimitHit(_) => {
+                "The stack grew larger than the stack limit allows.\n\nCheck for a loop \
+                 that pushes without popping, or raise the limit with \
+                 `--stack-limit`.\n\nExample: `1 0 goto` pushes forever."
+            },
+            Self::TimeLimitHit(_) => {
+                "The program ran for longer than the time limit allows.\n\nCheck for a \
+                 loop that never ends, or raise the limit with `--timeout`.\n\nExample: \
+                 `0 goto` with `--timeout 10`."
+            },
+            Self::NoOut => {
+                "The program finished with nothing on the stack to return.\n\nLeave the \
+                 result on the stack at the end of the program.\n\nExample: `1 drop` \
+                 leaves the stack empty."
+            },
+            Self::NoTokens => {
+                "The program has no tokens to run.\n\nCheck that the right file is being \
+                 run and that it isn't empty.\n\nExample: running an empty file."
+            },
+            Self::WriteFailed(_) => {
+                "An opperation like `top` or `dump` couldn't write its output.\n\nCheck \
+                 that the output, such as a pipe, is still open.\n\nExample: `1 top` \
+                 piped into a program that has exited."
+            },
+            Self::AssertionFailed(_) => {
+                "An assertion failed, either `asserteq` found two values that aren't equal \
+                 or `assertempty` found values left on the stack.\n\nCheck the values \
+                 leading up to the assertion.\n\nExample: `1 2 asserteq`."
+            },
+            Self::IndexOutOfBounds(_) => {
+                "An opperation was given an index or count outside of what it can \
+                 use.\n\nCheck the index against the size of the stack, data, or \
+                 registers it refers to.\n\nExample: `1 2 0 5 swapat` with only two values \
+                 on the stack."
+            },
+            Self::Overflow(_) => {
+                "An arithmetic result doesn't fit in a 64 bit integer.\n\nUse smaller \
+                 values, or check for runaway growth in a loop.\n\nExample: `3037000500 sq`."
+            },
+            Self::NegativeOperand(_) => {
+                "An opperation that only works on non-negative values was given a negative \
+                 one.\n\nCheck the sign of the value first, for example with `sign`.\n\n\
+                 Example: `-5 digits`."
+            },
+            Self::InvalidRange(_) => {
+                "The minimum of a range is above its maximum.\n\nPush the minimum before \
+                 the maximum.\n\nExample: `5 10 1 clamp`."
+            },
+            Self::UnknownCustomOp(_) => {
+                "A custom opperation was executed that isn't registered on the \
+                 runtime.\n\nRegister it with `Slug::register_op` before running the \
+                 program.\n\nExample: parsing with one runtime and running on another."
+            },
+            Self::DivByZero(_) => {
+                "A division had a divisor of zero.\n\nCheck the divisor before \
+                 dividing.\n\nExample: `7 0 fdiv`."
+            },
+            Self::JumpTooFar(_) => {
+                "A jump moved the execution pointer further than the maximum jump distance \
+                 allows.\n\nJump in smaller steps, or raise the limit with \
+                 `--max-jump`.\n\nExample: `100 hop` with `--max-jump 10`."
+            },
+            Self::ReadFailed(_) => {
+                "An opperation like `readlinelen` couldn't read its input.\n\nCheck that \
+                 the input, such as stdin, can be read.\n\nExample: stdin closed with an \
+                 error part way through a line."
+            },
+            Self::ProtectedUnderflow(_) => {
+                "An opperation tried to pop or rearrange a value protected with \
+                 `Slug::protect`.\n\nRead protected values without removing them, and \
+                 leave them at the bottom of the stack.\n\nExample: `drop` when the \
+                 only value on the stack is protected."
+            },
+            Self::LiteralTooLarge(_) => {
+                "A literal in the program is larger than the maximum literal allows.\n\n\
+                 Build large values up from smaller ones, or raise the limit with \
+                 `--max-literal`.\n\nExample: `1000 fill` with `--max-literal 100`."
+            },
+            Self::NonPositiveOperand(_) => {
+                "An opperation that only works on positive values was given zero or a \
+                 negative value.\n\nCheck the value is above zero first.\n\nExample: `0 \
+                 floorpow2`."
+            },
+            Self::MemoryLimitHit(_) => {
+                "The stack and memory together held more values than the memory limit \
+                 allows.\n\nCheck for loops that push or `spill` without ever popping, or \
+                 raise the limit with `--mem-limit`.\n\nExample: `1 spill 0 goto` with \
+                 `--mem-limit 100`."
+            },
+            Self::InputError(_) => {
+                "`in` found something other than an integer in the input, or the input \
+                 ended.\n\nCheck the input holds whitespace separated integers, one for \
+                 each `in`.\n\nExample: `in` with the input `abc`."
+            },
+            Self::AlreadyHalted => {
+                "A runtime was executed again after its program exited with a \
+                 value.\n\nCall `Slug::reset` first to run the program again.\n\nExample: \
+                 calling `execute` twice on a runtime running `1 2 add` with `eof` set."
+            },
+            Self::BadChar(_) => {
+                "`emit` was given a value that isn't a Unicode scalar value, such as a \
+                 negative value or a surrogate.\n\nCheck the value is a code point from \
+                 `0` to `1114111`, outside of `55296` to `57343`.\n\nExample: `-1 emit`."
+            },
+            Self::NonDeterministic(_) => {
+                "`now` was run in a sandbox, where opperations that could give a different \
+                 result each run are rejected.\n\nRemove the timing from the program, or run \
+                 it without `--sandbox`.\n\nExample: `now` with `--sandbox`."
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for err in RuntimeError::all() {
+            assert_eq!(RuntimeError::from_name(err.name()), Some(err));
+        }
+        assert_eq!(
+            RuntimeError::from_name("UnderRead"),
+            Some(&RuntimeError::UnderRead(0))
+        );
+        assert_eq!(RuntimeError::from_name("nonsense"), None);
+    }
+
+    #[test]
+    fn explain_underread() {
+        let err = RuntimeError::from_name("underread").expect("`underread` is an error name");
+        assert!(err.explain().contains("stack underflow"));
+    }
+
+    #[test]
+    fn token_index() {
+        assert_eq!(RuntimeError::DivByZero(4).token_index(), Some(4));
+        assert_eq!(RuntimeError::UnderRead(0).token_index(), Some(0));
+        assert_eq!(RuntimeError::NoOut.token_index(), None);
+        assert_eq!(RuntimeError::AlreadyHalted.token_index(), None);
+
+        assert_eq!(RuntimeError::DivByZero(4).at(9), RuntimeError::DivByZero(9));
+        assert_eq!(RuntimeError::NoOut.at(9), RuntimeError::NoOut);
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use crate::opp::Opp;
+use crate::token::Token;
+
+/// Where control can go after a token
+enum Flow {
+    /// Carries on to the listed tokens
+    To(Vec<i64>),
+    /// Jumps somewhere that can't be known without running the program
+    Computed,
+}
+
+/// Finds the indices of tokens that can never execute.
+///
+/// Control flow is followed from the first token through `goto`, `jz`, `hop`,
+/// `hopif`, and `exitif` when their operands are literals pushed directly
+/// before them. If any reachable jump has a computed target the analysis
+/// gives up and reports nothing, since that jump could land anywhere.
+#[must_use]
+pub fn unreachable_tokens(tokens : &[Token]) -> Vec<usize> {
+    let mut reachable = vec![false; tokens.len()];
+    // Tokens something jumps to, along with every jump that read literal
+    // operands from the tokens before it
+    let mut jumped_to = HashSet::new();
+    let mut literal_jumps = Vec::new();
+
+    let mut work = vec![0];
+    while let Some(idx) = work.pop() {
+        let Some(token) = tokens.get(idx) else {
+            continue;
+        };
+        if reachable[idx] {
+            continue;
+        }
+        reachable[idx] = true;
+
+        let (flow, operands) = flow(tokens, idx, token);
+        if operands > 0 {
+            literal_jumps.push((idx, operands));
+        }
+
+        let Flow::To(targets) = flow else {
+            return Vec::new();
+        };
+
+        #[expect(
+            clippy::cast_possible_wrap,
+            reason = "No program has anywhere near i64::MAX tokens"
+        )]
+        let next = idx as i64 + 1;
+        for target in targets {
+            if target != next {
+                jumped_to.insert(target);
+            }
+            if let Ok(target) = usize::try_from(target) {
+                work.push(target);
+            }
+        }
+    }
+
+    // A jump whose operands can be jumped over might not be reading the
+    // literals at all
+    for (idx, operands) in literal_jumps {
+        #[expect(
+            clippy::cast_possible_wrap,
+            reason = "No program has anywhere near i64::MAX tokens"
+        )]
+        let skipped = (idx + 1 - operands ..= idx).any(|i| jumped_to.contains(&(i as i64)));
+        if skipped {
+            return Vec::new();
+        }
+    }
+
+    reachable
+        .iter()
+        .enumerate()
+        .filter(|(_, reached)| !**reached)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Works out how many loops each token is inside, for indenting.
+///
+/// A loop runs from the target of a backward `goto`, `jz`, `hop`, or `hopif` up
+/// to and including the jump, when the target is a literal pushed directly
+/// before the jump. For `hopif` the offset must come directly before a single
+/// token condition, which doesn't need to be a literal.
+#[must_use]
+pub fn loop_depths(tokens : &[Token]) -> Vec<usize> {
+    let mut depths = vec![0; tokens.len()];
+    for idx in 0 .. tokens.len() {
+        let Some(target) = backward_target(tokens, idx) else {
+            continue;
+        };
+        for depth in &mut depths[target ..= idx] {
+            *depth += 1;
+        }
+    }
+    depths
+}
+
+/// The token a jump at `idx` lands on, if it's a jump with a literal target
+/// at or before itself
+fn backward_target(tokens : &[Token], idx : usize) -> Option<usize> {
+    #[expect(
+        clippy::cast_possible_wrap,
+        reason = "No program has anywhere near i64::MAX tokens"
+    )]
+    let here = idx as i64;
+    let literal = |back : usize| {
+        match idx.checked_sub(back).map(|i| &tokens[i]) {
+            Some(Token::Value(v)) => Some(*v),
+            _ => None,
+        }
+    };
+
+    let target = match tokens[idx] {
+        Token::Opp(Opp::Goto | Opp::Jz) => literal(1)?,
+        Token::Opp(Opp::Hop) => hop_target(here, literal(1)?)?,
+        Token::Opp(Opp::HopIf) => hop_target(here, literal(2)?)?,
+        _ => return None,
+    };
+    usize::try_from(target).ok().filter(|target| *target <= idx)
+}
+
+/// The token a `hop` or `hopif` at `here` lands on when it moves by `offset`,
+/// or `None` if that's too far out of the program to count
+fn hop_target(here : i64, offset : i64) -> Option<i64> {
+    here.checked_add(offset)
+        .and_then(|target| target.checked_add(1))
+}
+
+/// Works out where control goes after the token at `idx`, and how many of the
+/// tokens before it were used as literal operands to work that out
+#[expect(
+    clippy::option_if_let_else,
+    reason = "Clippy's 'solution' is much less readable"
+)]
+fn flow(tokens : &[Token], idx : usize, token : &Token) -> (Flow, usize) {
+    #[expect(
+        clippy::cast_possible_wrap,
+        reason = "No program has anywhere near i64::MAX tokens"
+    )]
+    let here = idx as i64;
+    let literal = |back : usize| {
+        match idx.checked_sub(back).map(|i| &tokens[i]) {
+            Some(Token::Value(v)) => Some(*v),
+            _ => None,
+        }
+    };
+
+    match token {
+        Token::Opp(Opp::Exit) => (Flow::To(Vec::new()), 0),
+        // Only known to exit when the condition is a literal
+        Token::Opp(Opp::ExitIf) => {
+            match literal(1) {
+                Some(cond) if cond != 0 => (Flow::To(Vec::new()), 1),
+                _ => (Flow::To(vec![here + 1]), 0),
+            }
+        },
+        // `goto n` lands on token `n`
+        Token::Opp(Opp::Goto) => {
+            match literal(1) {
+                Some(target) => (Flow::To(vec![target]), 1),
+                None => (Flow::Computed, 0),
+            }
+        },
+        // `hop` moves the pointer by the offset before stepping past itself
+        Token::Opp(Opp::Hop) => {
+            match literal(1) {
+                // Landing out of the program ends it with an error
+                Some(offset) => (Flow::To(hop_target(here, offset).into_iter().collect()), 1),
+                None => (Flow::Computed, 0),
+            }
+        },
+        // The offset is only known when the condition is a literal too
+        Token::Opp(Opp::HopIf) => {
+            match (literal(2), literal(1)) {
+                (Some(_), Some(0)) => (Flow::To(vec![here + 1]), 2),
+                (Some(offset), Some(_)) => {
+                    (Flow::To(hop_target(here, offset).into_iter().collect()), 2)
+                },
+                _ => (Flow::Computed, 0),
+            }
+        },
+        // Like `goto` when the condition is `0`, and either way when it isn't
+        // a literal
+        Token::Opp(Opp::Jz) => {
+            match (literal(2), literal(1)) {
+                (Some(0), Some(target)) => (Flow::To(vec![target]), 2),
+                (Some(_), Some(_)) => (Flow::To(vec![here + 1]), 2),
+                (None, Some(target)) => (Flow::To(vec![target, here + 1]), 1),
+                (_, None) => (Flow::Computed, 0),
+            }
+        },
+        _ => (Flow::To(vec![here + 1]), 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::Tokenizer;
+
+    fn unreachable(text : &str) -> Vec<usize> {
+        unreachable_tokens(&Tokenizer::parse_text(text).expect("Test programs should parse"))
+    }
+
+    #[test]
+    fn after_exit() {
+        assert_eq!(unreachable("1 exit 2 3 add"), vec![2, 3, 4]);
+        assert_eq!(unreachable("1 2 add"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn computed_jump() {
+        assert_eq!(unreachable("1 1 add goto 4 exit 5"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn huge_hops() {
+        assert_eq!(unreachable("9223372036854775807 hop 1"), vec![2]);
+        assert_eq!(unreachable("9223372036854775807 1 hopif 1"), vec![3]);
+    }
+}
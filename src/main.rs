@@ -1,4 +1,8 @@
-use std::fs::File;
+use std::collections::HashMap;
+use std::fs::{
+    self,
+    File,
+};
 use std::io::{
     self,
     BufRead,
@@ -7,14 +11,54 @@ use std::io::{
     Write,
     stdin,
 };
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{
+    Duration,
+    Instant,
+};
 
-use clap::Parser;
+use clap::{
+    Args,
+    Parser,
+    ValueEnum,
+};
 
-use crate::error::ExecutionError;
-use crate::runtime::Slug;
-use crate::token::Tokenizer;
+use crate::bytecode::{
+    MAGIC,
+    from_bytes,
+    to_bytes,
+};
+use crate::compile::compile_infix;
+use crate::error::{
+    ExecutionError,
+    ParseTextError,
+    RuntimeError,
+};
+use crate::lint::{
+    loop_depths,
+    unreachable_tokens,
+};
+use crate::opp::{
+    Opp,
+    instruction_set_json,
+    json_string,
+};
+use crate::runtime::{
+    ArithMode,
+    Slug,
+    TraceEvent,
+};
+use crate::token::{
+    Program,
+    Span,
+    Tokenizer,
+};
 
+pub mod bytecode;
+pub mod compile;
 pub mod error;
+pub mod lint;
 pub mod opp;
 pub mod runtime;
 pub mod token;
@@ -26,91 +70,581 @@ pub struct Cli {
     pub command : Subcommand,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Args)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "These are independent command line flags"
+)]
+pub struct RunArgs {
+    /// File to take as input to run.
+    pub file :          String,
+    /// Maximimum number of tokens executed, useful to debug infinite
+    /// recursion.
+    #[arg(short, long, env = "SLUG_TOKEN_LIMIT")]
+    pub token_limit :   Option<usize>,
+    /// Maximum size of the stack.
+    #[arg(short, long, env = "SLUG_STACK_LIMIT")]
+    pub stack_limit :   Option<usize>,
+    /// Maximum number of values the stack and memory may hold between them.
+    #[arg(long, env = "SLUG_MEM_LIMIT")]
+    pub mem_limit :     Option<usize>,
+    /// Maximum time to run for, in milliseconds.
+    #[arg(long, env = "SLUG_TIMEOUT")]
+    pub timeout :       Option<u64>,
+    /// Maximum distance `goto`, `hop`, and `hopif` may move the pointer in
+    /// one jump.
+    #[arg(long)]
+    pub max_jump :      Option<i64>,
+    /// Largest absolute value a literal or data section value in the program
+    /// may have.
+    #[arg(long)]
+    pub max_literal :   Option<i64>,
+    /// Pop the left hand side of `sub`, `div`, `fdiv`, `mod`, `eq`, `lt`, and
+    /// `gt` first, so `5 3 sub` is `-2` rather than `2`.
+    #[arg(long)]
+    pub reverse_ops :   bool,
+    /// How `add`, `sub`, and `mul` handle results too large for 64 bits.
+    #[arg(long, value_enum, default_value_t = ArithMode::Checked)]
+    pub arith :         ArithMode,
+    /// Print the whole final stack, bottom to top, rather than just the top
+    /// value.
+    #[arg(long)]
+    pub all :           bool,
+    /// How to print the result.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format :        OutputFormat,
+    /// Extra characters to separate tokens by alongside whitespace, for
+    /// example `--sep ',;'`.
+    #[arg(long, default_value = "")]
+    pub sep :           String,
+    /// Print the stack to stderr if the program errors part way through.
+    #[arg(long)]
+    pub dump_on_error : bool,
+    /// Reject opperations whose results differ between runs, like `now`, so
+    /// the program gives the same result every time.
+    #[arg(long)]
+    pub sandbox :       bool,
+    /// After the file, keep reading tokens from stdin a line at a time,
+    /// printing the top of the stack after each line.
+    #[arg(short, long)]
+    pub interactive :   bool,
+    /// Print how long was spent in each opperation to stderr afterwards.
+    #[arg(long)]
+    pub time_ops :      bool,
+    /// Write every executed token to this file in the Chrome tracing JSON
+    /// format, for viewing in `chrome://tracing`.
+    #[arg(long)]
+    pub trace_out :     Option<String>,
+    /// Start with the memory holding the whitespace separated integers in
+    /// this file. They count toward `--mem-limit`.
+    #[arg(long)]
+    pub mem_file :      Option<String>,
+    /// Re-run the file whenever it changes.
+    #[cfg(feature = "watch")]
+    #[arg(long)]
+    pub watch :         bool,
+}
+
+impl RunArgs {
+    /// Creates a runtime configured with these options
+    #[must_use]
+    pub fn runtime(&self) -> Slug {
+        Slug {
+            token_limit : self.token_limit,
+            stack_limit : self.stack_limit,
+            time_limit : self.timeout.map(Duration::from_millis),
+            total_cell_limit : self.mem_limit,
+            max_jump : self.max_jump,
+            max_literal : self.max_literal,
+            eof : true,
+            reverse_operands : self.reverse_ops,
+            arith_mode : self.arith,
+            deterministic : self.sandbox,
+            op_times : self.time_ops.then(HashMap::new),
+            trace : self.trace_out.is_some().then(Vec::new),
+            ..Slug::new()
+        }
+    }
+
+    #[must_use]
+    pub fn separators(&self) -> Vec<char> {
+        self.sep.chars().collect()
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct FmtArgs {
+    /// File to format
+    pub file :                 String,
+    /// Whether to pad with newlines or spaces
+    #[arg(short, long)]
+    pub new_lines :            Option<bool>,
+    /// Output file of formatting, defaults to the input file.
+    #[arg(long)]
+    pub out :                  Option<String>,
+    /// Extra characters to separate tokens by alongside whitespace.
+    #[arg(long, default_value = "")]
+    pub sep :                  String,
+    /// Keep blank lines between groups of tokens.
+    #[arg(long)]
+    pub preserve_blank_lines : bool,
+    /// Indent the tokens of loops made with backward jumps, when padding
+    /// with newlines.
+    #[arg(long)]
+    pub indent_loops :         bool,
+}
+
 #[derive(Debug, Parser)]
 pub enum Subcommand {
     /// Run a file.
-    Run {
-        /// File to take as input to run.
+    Run(RunArgs),
+    /// Formats a file.
+    Fmt(FmtArgs),
+    /// Creates a Repl to test out the syntax and the control flow.
+    Repl {
+        /// Don't print the banner on startup.
+        #[arg(long)]
+        no_banner : bool,
+        /// Prompt shown before each line, with `{depth}` replaced by the
+        /// number of values on the stack.
+        #[arg(long, default_value = "slug[{depth}]> ")]
+        prompt :    String,
+    },
+    /// Runs a file repeatedly and reports its throughput.
+    Bench {
+        /// File to benchmark.
+        file :       String,
+        /// Number of times to run the file, at least 1.
+        #[arg(short, long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1 ..))]
+        iterations : u64,
+    },
+    /// Compiles an infix arithmetic expression, like `(2 + 3) * 4`, into Slug.
+    Compile {
+        /// Expression to compile.
+        #[arg(allow_hyphen_values = true)]
+        expr : String,
+    },
+    /// Runs a file and checks its result, exiting with a failure if it
+    /// doesn't match.
+    Test {
+        /// File to run.
         file :        String,
-        /// Maximimum number of tokens executed, useful to debug infinite
-        /// recursion.
-        #[arg(short, long)]
+        /// The result the file should return.
+        #[arg(long, allow_hyphen_values = true)]
+        expect :      i64,
+        /// Maximimum number of tokens executed.
+        #[arg(short, long, env = "SLUG_TOKEN_LIMIT")]
         token_limit : Option<usize>,
-        /// Maximum size of the stack.
-        #[arg(short, long)]
-        stack_limit : Option<usize>,
     },
-    /// Formats a file.
-    Fmt {
-        /// File to format
-        file :      String,
-        /// Whether to pad with newlines or spaces
+    /// Runs every `.slug` file in a directory, checking each against the
+    /// result in the file of the same name with the expected extension.
+    RunAll {
+        /// Directory of programs to run.
+        dir :         String,
+        /// Extension of the files holding the expected results.
+        #[arg(long, default_value = "expected")]
+        expect_file : String,
+        /// Maximimum number of tokens executed by each program.
+        #[arg(short, long, env = "SLUG_TOKEN_LIMIT")]
+        token_limit : Option<usize>,
+    },
+    /// Runs built in example programs to check the interpreter works.
+    Selftest,
+    /// Prints every opperation as JSON, for tooling.
+    #[command(hide = true)]
+    Spec,
+    /// Warns about tokens in a file that can never execute.
+    Lint {
+        /// File to check.
+        file : String,
+    },
+    /// Runs files one after another on the same stack, so each sees what the
+    /// one before it left behind.
+    Pipeline {
+        /// Files to run, in order.
+        #[arg(required = true)]
+        files :       Vec<String>,
+        /// Maximimum number of tokens executed across every file.
+        #[arg(short, long, env = "SLUG_TOKEN_LIMIT")]
+        token_limit : Option<usize>,
+    },
+    /// Explains a kind of runtime error in depth, such as `underread`.
+    Explain {
+        /// Name of the error.
+        code : String,
+    },
+    /// Assembles a file into a compact binary form that `run` loads directly.
+    Assemble {
+        /// File to assemble.
+        file : String,
+        /// Output file, defaults to the input file with a `.slugc` extension.
         #[arg(short, long)]
-        new_lines : Option<bool>,
-        /// Output file of formatting, defaults to the input file.
-        #[arg(long)]
-        out :       Option<String>,
+        out :  Option<String>,
     },
-    /// Creates a Repl to test out the syntax and the control flow.
-    Repl,
 }
 
-fn main() -> io::Result<()> {
+#[expect(clippy::too_many_lines, reason = "Boo Hoo Clippy")]
+fn main() -> io::Result<ExitCode> {
     let args = Cli::parse();
 
     match args.command {
-        Subcommand::Run {
+        Subcommand::Run(args) => {
+            #[cfg(feature = "watch")]
+            if args.watch {
+                watch_file(&args).map_err(io::Error::other)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if let Err(err) = run_once(&args) {
+                eprintln!("{err}");
+            }
+        },
+        Subcommand::Fmt(args) => {
+            println!("Formatting {}", args.file);
+
+            format_file(&args)?;
+        },
+        Subcommand::Repl {
+            no_banner,
+            prompt,
+        } => repl(!no_banner, &prompt)?,
+        Subcommand::Bench {
             file,
+            iterations,
+        } => {
+            match bench_file(&file, iterations) {
+                Ok(report) => {
+                    eprintln!(
+                        "Executed {} tokens in {:?} ({:.0} tokens/sec)",
+                        report.tokens,
+                        report.elapsed,
+                        report.throughput()
+                    );
+                },
+                Err(err) => eprintln!("{err}"),
+            }
+        },
+        Subcommand::Compile {
+            expr,
+        } => {
+            match compile_infix(&expr) {
+                Ok(tokens) => {
+                    let text : Vec<String> = tokens.iter().map(ToString::to_string).collect();
+                    println!("{}", text.join(" "));
+                },
+                Err(err) => eprintln!("{err}"),
+            }
+        },
+        Subcommand::Selftest => return Ok(selftest()),
+        Subcommand::Spec => println!("{}", instruction_set_json()),
+        Subcommand::Test {
+            file,
+            expect,
+            token_limit,
+        } => return Ok(test_file(&file, expect, token_limit)),
+        Subcommand::RunAll {
+            dir,
+            expect_file,
+            token_limit,
+        } => return run_all(&dir, &expect_file, token_limit),
+        Subcommand::Lint {
+            file,
+        } => {
+            match load_file(&file, &[]) {
+                Ok(program) => {
+                    for idx in unreachable_tokens(&program.tokens) {
+                        let token = program.tokens[idx];
+                        println!("warning: token {idx} `{token}` can never execute");
+                    }
+                },
+                Err(err) => eprintln!("{err}"),
+            }
+        },
+        Subcommand::Pipeline {
+            files,
             token_limit,
-            stack_limit,
         } => {
-            match run_file(&file, token_limit, stack_limit) {
-                Ok(out) => println!("Result: {out}"),
-                Err(err) => eprintln!("{err:?}"),
+            let mut runtime = Slug {
+                token_limit,
+                ..Slug::new()
+            };
+            match run_pipeline(&files, &mut runtime) {
+                Ok(out) => print_result(&mut runtime, out, false, OutputFormat::Text),
+                Err(err) => eprintln!("{err}"),
             }
         },
-        Subcommand::Fmt {
+        Subcommand::Explain {
+            code,
+        } => {
+            let Some(err) = RuntimeError::from_name(&code) else {
+                let names : Vec<&str> =
+                    RuntimeError::all().iter().map(RuntimeError::name).collect();
+                eprintln!(
+                    "Unknown error `{code}`, expected one of: {}",
+                    names.join(", ")
+                );
+                return Ok(ExitCode::FAILURE);
+            };
+            println!("{}", err.explain());
+        },
+        Subcommand::Assemble {
             file,
-            new_lines,
             out,
         } => {
-            println!("Formatting {file}");
-
-            format_file(&file, new_lines, out)?;
+            let out = out.unwrap_or_else(|| {
+                Path::new(&file)
+                    .with_extension("slugc")
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            if let Err(err) = assemble_file(&file, &out) {
+                eprintln!("{err}");
+            }
         },
-        Subcommand::Repl => {
-            let mut input = stdin().lock();
-            let mut runtime = Slug::new();
-            loop {
-                let mut buf = String::new();
-                let out = match input.read_line(&mut buf) {
-                    Ok(0) => {
-                        let toks = Tokenizer::parse_text(&buf).expect("Unable to parse text");
-                        runtime.eof = true;
-                        runtime
-                            .execute_tokens(toks)
-                            .expect("Error during execution")
-                    },
-                    Ok(_) => {
-                        let toks = Tokenizer::parse_text(&buf).expect("Unable to parse text");
-                        runtime
-                            .execute_tokens(toks)
-                            .expect("Error during execution")
-                    },
-                    Err(e) => return Err(e),
-                };
-
-                if let Some(val) = out {
-                    println!("{val}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reads lines from stdin and runs them as they come in, until the program
+/// exits or the input ends. Errors are reported and the offending line is
+/// rolled back.
+///
+/// `prompt` is shown before each line, see `repl_prompt`.
+///
+/// # Errors
+/// Errors if stdin can't be read
+pub fn repl(banner : bool, prompt : &str) -> io::Result<()> {
+    // Not locked for the whole loop, since opperations like `readlinelen`
+    // read from stdin too
+    let input = stdin();
+    let mut runtime = Slug::new();
+
+    // The banner and prompt go to stderr so only results are on stdout
+    if banner {
+        eprintln!(
+            "Slug {}, enter tokens to run them and end the input to finish",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    loop {
+        eprint!("{}", repl_prompt(prompt, &runtime));
+        io::stderr().flush()?;
+
+        let mut buf = String::new();
+        if input.read_line(&mut buf)? == 0 {
+            runtime.eof = true;
+        }
+
+        let toks = match Tokenizer::parse_text_all(&buf) {
+            Ok(toks) => toks,
+            Err(errs) => {
+                for err in errs {
+                    eprintln!("{err}");
+                }
+                continue;
+            },
+        };
+
+        match runtime.execute_tokens(toks) {
+            Ok(Some(val)) => {
+                println!("{val}");
+                break;
+            },
+            Ok(None) => {},
+            Err(err) => {
+                eprintln!("{err}");
+                if runtime.eof {
                     break;
                 }
-            }
-        },
+                runtime.rollback();
+            },
+        }
     }
 
     Ok(())
 }
 
+/// Fills in the Repl's prompt from the state of the runtime
+#[must_use]
+pub fn repl_prompt(prompt : &str, runtime : &Slug) -> String {
+    prompt.replace("{depth}", &runtime.stack.len().to_string())
+}
+
+/// Runs a file and reports whether it returned `expected`
+#[must_use]
+pub fn test_file(file : &str, expected : i64, token_limit : Option<usize>) -> ExitCode {
+    if check_file(file, expected, token_limit, io::stdout()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs a file, writing to `out` whether it returned `expected`. Failing to
+/// write the report doesn't change the result.
+fn check_file(
+    file : &str,
+    expected : i64,
+    token_limit : Option<usize>,
+    mut out : impl Write,
+) -> bool {
+    let mut runtime = Slug {
+        token_limit,
+        eof : true,
+        ..Slug::new()
+    };
+
+    match run_file(file, &[], &mut runtime) {
+        Ok(result) if result == expected => {
+            let _ = writeln!(out, "ok: {file} returned {result}");
+            true
+        },
+        Ok(result) => {
+            let _ = writeln!(out, "FAILED: {file} returned {result}, expected {expected}");
+            false
+        },
+        Err(err) => {
+            let _ = writeln!(out, "FAILED: {file} errored with {err}");
+            false
+        },
+    }
+}
+
+/// Runs every `.slug` file in a directory, in order of name, and reports how
+/// many passed.
+///
+/// Each file is checked against the value in the file next to it with the
+/// extension `expect_ext`, so `sum.slug` is checked against `sum.expected` by
+/// default.
+///
+/// # Errors
+/// Errors if the directory can't be read
+pub fn run_all(dir : &str, expect_ext : &str, token_limit : Option<usize>) -> io::Result<ExitCode> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "slug") {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut failed = 0;
+    for path in &files {
+        let file = path.to_string_lossy();
+        let expected_path = path.with_extension(expect_ext);
+        let expected = fs::read_to_string(&expected_path)
+            .ok()
+            .and_then(|text| text.trim().parse::<i64>().ok());
+
+        let Some(expected) = expected else {
+            println!(
+                "FAILED: {file} has no valid expected result in {}",
+                expected_path.display()
+            );
+            failed += 1;
+            continue;
+        };
+        if !check_file(&file, expected, token_limit, io::stdout()) {
+            failed += 1;
+        }
+    }
+
+    println!("{} passed, {failed} failed", files.len() - failed);
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Example programs run by `selftest`, along with what they should return
+const SELFTEST_PROGRAMS : &[(&str, &str, Result<i64, RuntimeError>)] = &[
+    ("arithmetic", "2 3 add 4 mul 6 sub", Ok(14)),
+    // Sums 5 down to 1 in register 1, counting down in register 0 and
+    // hopping back while it's nonzero
+    (
+        "loop",
+        "5 0 set 0 get 1 get add 1 set 0 get 1 sub 0 set -17 0 get hopif 1 get",
+        Ok(15),
+    ),
+    ("goto", "3 goto 99 7", Ok(7)),
+    // Counts register 0 down from 5, leaving the loop with `jz` once it's zero
+    (
+        "countdown",
+        "5 0 set 0 get 1 sub dup 0 set 14 jz 3 goto 0 get",
+        Ok(0),
+    ),
+    ("dup", "3 dup mul", Ok(9)),
+    ("comparison", "3 3 eq 2 5 lt add 2 5 gt add", Ok(2)),
+    // Pushes the remainder and then the quotient
+    ("division", "7 2 div", Ok(3)),
+    ("modulo", "17 5 mod", Ok(2)),
+    (
+        "floor division by zero",
+        "7 0 fdiv",
+        Err(RuntimeError::DivByZero(2)),
+    ),
+    (
+        "division by zero",
+        "5 0 div",
+        Err(RuntimeError::DivByZero(2)),
+    ),
+    (
+        "overflow",
+        "9223372036854775807 1 add",
+        Err(RuntimeError::Overflow(2)),
+    ),
+];
+
+/// Runs the example programs in `SELFTEST_PROGRAMS`, reporting whether each
+/// gave the expected result
+#[must_use]
+pub fn selftest() -> ExitCode {
+    let mut passed = true;
+    for (name, text, expected) in SELFTEST_PROGRAMS {
+        let mut runtime = Slug {
+            token_limit : Some(1000),
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+
+        let got = match Tokenizer::parse_text(text) {
+            Ok(tokens) => {
+                runtime.tokens = tokens;
+                runtime.execute().map(Option::unwrap_or_default)
+            },
+            Err(err) => {
+                println!("FAILED: {name} doesn't parse, {err}");
+                passed = false;
+                continue;
+            },
+        };
+
+        if got == *expected {
+            println!("ok: {name}");
+        } else {
+            println!("FAILED: {name} gave {got:?}, expected {expected:?}");
+            passed = false;
+        }
+    }
+
+    if passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
 /// Formats a file with optional parameters
 ///
 /// # Errors
@@ -118,18 +652,30 @@ fn main() -> io::Result<()> {
 ///
 /// # Panics
 /// Panics if the inputed file can't be parsed.
-pub fn format_file(file : &str, new_lines : Option<bool>, out : Option<String>) -> io::Result<()> {
+pub fn format_file(args : &FmtArgs) -> io::Result<()> {
+    let file = &args.file;
     match File::options().write(true).read(true).open(file) {
         Ok(mut data) => {
             let mut buf = String::new();
 
             data.read_to_string(&mut buf)?;
 
-            let tokens = Tokenizer::parse_text(&buf).expect("Unable to parse text");
-
             drop(data);
 
-            let mut out = match out {
+            let text = match format_text(&buf, args) {
+                Ok(text) => text,
+                Err(errs) => {
+                    for err in &errs {
+                        eprintln!("{err}");
+                    }
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Unable to parse {file}"),
+                    ));
+                },
+            };
+
+            let mut out = match args.out.clone() {
                 Some(path) => {
                     match File::options()
                         .write(true)
@@ -158,23 +704,305 @@ pub fn format_file(file : &str, new_lines : Option<bool>, out : Option<String>)
 
             out.lock()?;
 
-            let whitespace = if new_lines.unwrap_or(true) { "\n" } else { " " };
+            out.write_all(text.as_bytes())?;
+        },
+
+        Err(err) => return Err(err),
+    }
+    Ok(())
+}
+
+/// Formats the text of a program as set out by `args`, keeping any shebang
+///
+/// # Errors
+/// Errors if the text can't be parsed, with an error for each problem
+pub fn format_text(text : &str, args : &FmtArgs) -> Result<String, Vec<ParseTextError>> {
+    let (shebang, text) = Tokenizer::split_shebang(text);
+
+    let separators : Vec<char> = args.sep.chars().collect();
+    let whitespace = if args.new_lines.unwrap_or(true) {
+        "\n"
+    } else {
+        " "
+    };
+
+    let (program, spans) = Tokenizer::parse_program_spanned(text, &separators)?;
+    let depths = if args.indent_loops && whitespace == "\n" {
+        loop_depths(&program.tokens)
+    } else {
+        Vec::new()
+    };
+
+    let formatted = if args.preserve_blank_lines {
+        let breaks = paragraph_starts(text, &spans);
+        format_program(&program, whitespace, &depths, &breaks)
+            .trim_end()
+            .to_owned()
+            + "\n"
+    } else {
+        format_program(&program, whitespace, &depths, &[])
+    };
+    Ok(shebang.unwrap_or_default().to_owned() + &formatted)
+}
+
+/// Finds the indices of tokens that start a paragraph, those with a blank line
+/// between them and the token before
+fn paragraph_starts(text : &str, spans : &[Span]) -> Vec<usize> {
+    spans
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            // The lines between the two tokens, ignoring the partial lines they
+            // are on
+            let gap = &text[pair[0].end .. pair[1].start];
+            let mut lines : Vec<&str> = gap.split('\n').collect();
+            lines.pop();
+            lines.iter().skip(1).any(|line| line.trim().is_empty())
+        })
+        .map(|(idx, _)| idx + 1)
+        .collect()
+}
+
+/// Formats a program's data and tokens, following each with `whitespace`.
+/// Each token is indented by two spaces per level of its entry in `depths`,
+/// and a blank line is left before each token in `breaks`.
+fn format_program(
+    program : &Program,
+    whitespace : &str,
+    depths : &[usize],
+    breaks : &[usize],
+) -> String {
+    let mut text = String::new();
+    if !program.data.is_empty() {
+        let data : Vec<String> = program.data.iter().map(ToString::to_string).collect();
+        text += "data ";
+        text += &data.join(" ");
+        text += " end";
+        text += whitespace;
+    }
+    for (idx, token) in program.tokens.iter().enumerate() {
+        if breaks.contains(&idx) {
+            text.truncate(text.trim_end().len());
+            text += "\n\n";
+        }
+        let depth = depths.get(idx).copied().unwrap_or_default();
+        text += &"  ".repeat(depth);
+        text += &format!("{token}").to_string();
+        text += whitespace;
+    }
+    text
+}
 
-            let mut text = String::new();
-            for token in tokens {
-                text += &format!("{token}").to_string();
-                text += whitespace;
+/// Runs a file once with the given options, printing the result if it
+/// succeeds
+///
+/// # Errors
+/// See `run_file`
+pub fn run_once(args : &RunArgs) -> Result<i64, ExecutionError> {
+    if args.format == OutputFormat::Text {
+        println!("Running {}", args.file);
+    }
+
+    let mut runtime = args.runtime();
+    if let Some(path) = &args.mem_file {
+        runtime.memory = load_memory(path)?;
+    }
+    let out = if args.interactive {
+        run_interactive(&args.file, &args.separators(), &mut runtime)
+    } else {
+        run_file(&args.file, &args.separators(), &mut runtime)
+    };
+    if let Some(times) = &runtime.op_times {
+        print_op_times(times);
+    }
+    if let (Some(path), Some(trace)) = (&args.trace_out, &runtime.trace) {
+        fs::write(path, trace_json(trace))?;
+    }
+    let out = match out {
+        Ok(out) => out,
+        Err(err @ ExecutionError::RuntimeError(_)) => {
+            if args.dump_on_error {
+                eprint!("{}", runtime.describe());
             }
+            return Err(err);
+        },
+        Err(err) => return Err(err),
+    };
+    print_result(&mut runtime, out, args.all, args.format);
 
-            out.write_all(text.as_bytes())?;
+    Ok(out)
+}
+
+/// Reads a memory image, whitespace separated integers with the first being
+/// at address `0`
+///
+/// # Errors
+/// Errors if the file can't be read or holds something other than integers
+pub fn load_memory(path : &str) -> io::Result<Vec<i64>> {
+    fs::read_to_string(path)?
+        .split_ascii_whitespace()
+        .map(|word| {
+            word.parse().map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("`{word}` in the memory image isn't an integer"),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Formats a trace as Chrome tracing JSON, one complete event per token with
+/// times in microseconds
+#[must_use]
+pub fn trace_json(trace : &[TraceEvent]) -> String {
+    let events : Vec<String> = trace
+        .iter()
+        .map(|event| {
+            format!(
+                "{{ \"name\": {}, \"ph\": \"X\", \"ts\": {:.3}, \"dur\": {:.3}, \"pid\": 1, \
+                 \"tid\": 1, \"args\": {{ \"index\": {} }} }}",
+                json_string(&event.token.to_string()),
+                event.start.as_secs_f64() * 1e6,
+                event.duration.as_secs_f64() * 1e6,
+                event.index
+            )
+        })
+        .collect();
+
+    format!("[\n  {}\n]\n", events.join(",\n  "))
+}
+
+/// Prints the time spent in each opperation to stderr, slowest first
+fn print_op_times(times : &HashMap<Opp, Duration>) {
+    let mut times : Vec<(&Opp, &Duration)> = times.iter().collect();
+    times.sort_by(|a, b| b.1.cmp(a.1));
+
+    let total : Duration = times.iter().map(|(_, time)| **time).sum();
+    eprintln!("Time per opperation, {total:?} in total:");
+    for (op, time) in times {
+        eprintln!("{:>12} {time:?}", op.to_string());
+    }
+}
+
+/// Runs a file, then clears the screen and runs it again every time it
+/// changes. Errors are printed and watching carries on.
+///
+/// # Errors
+/// Errors if the file can't be watched
+#[cfg(feature = "watch")]
+pub fn watch_file(args : &RunArgs) -> notify::Result<()> {
+    use std::sync::mpsc;
+
+    use notify::{
+        RecursiveMode,
+        Watcher,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(Path::new(&args.file), RecursiveMode::NonRecursive)?;
+
+    if let Err(err) = run_once(args) {
+        eprintln!("{err}");
+    }
+
+    for event in rx {
+        if !event?.kind.is_modify() {
+            continue;
+        }
+
+        // Clear the screen and move the cursor to the top left
+        print!("\x1B[2J\x1B[1;1H");
+        if let Err(err) = run_once(args) {
+            eprintln!("{err}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the result of a successful run
+fn print_result(runtime : &mut Slug, out : i64, all : bool, format : OutputFormat) {
+    print!("{}", format_result(runtime, out, all, format));
+}
+
+/// Formats the result of a successful run, one line per value with `all`
+fn format_result(runtime : &mut Slug, out : i64, all : bool, format : OutputFormat) -> String {
+    let values = if all {
+        // The top was already popped as the result, put it back
+        runtime.stack.push(out);
+        runtime.exit_all().unwrap_or_default()
+    } else {
+        vec![out]
+    };
+
+    match format {
+        OutputFormat::Text if all => {
+            let lines : Vec<String> = values.iter().map(ToString::to_string).collect();
+            lines.join("\n") + "\n"
         },
+        OutputFormat::Text => format!("Result: {out}\n"),
+        OutputFormat::Json => {
+            let result = if all {
+                let values : Vec<String> = values.iter().map(ToString::to_string).collect();
+                format!("[{}]", values.join(", "))
+            } else {
+                out.to_string()
+            };
+            format!(
+                "{{ \"result\": {result}, \"tokens\": {}, \"peak_stack\": {} }}\n",
+                runtime.tokens_consumed, runtime.peak_stack
+            )
+        },
+    }
+}
 
-        Err(err) => return Err(err),
+/// Reads and tokenizes a file, splitting tokens on whitespace and any of the
+/// `separators`. A leading `#!` line is skipped, and files assembled with
+/// `assemble` are decoded instead.
+///
+/// # Errors
+/// This function will error if the file can't be opened and read, or if the
+/// file is syntaxtically invalid
+pub fn load_file(file : &str, separators : &[char]) -> Result<Program, ExecutionError> {
+    let mut buf = Vec::new();
+    File::open(file)?.read_to_end(&mut buf)?;
+
+    if buf.starts_with(MAGIC) {
+        return Ok(Program {
+            tokens : from_bytes(&buf)?,
+            data :   Vec::new(),
+        });
     }
+
+    let text = String::from_utf8(buf).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    let (_, text) = Tokenizer::split_shebang(&text);
+    Ok(Tokenizer::parse_program(text, separators)?)
+}
+
+/// Assembles a program into the binary format read by `load_file`, writing it
+/// to `out`
+///
+/// # Errors
+/// This function will error if the file can't be read or parsed, if it has
+/// data sections, which the binary format can't hold, or if `out` can't be
+/// written
+pub fn assemble_file(file : &str, out : &str) -> Result<(), ExecutionError> {
+    let program = load_file(file, &[])?;
+    if !program.data.is_empty() {
+        return Err(ExecutionError::IoError(io::Error::new(
+            ErrorKind::Unsupported,
+            "Data sections can't be assembled",
+        )));
+    }
+
+    fs::write(out, to_bytes(&program.tokens))?;
     Ok(())
 }
 
-/// Runs a file with some optional parameters
+/// Runs a file on the given runtime, which should already be configured with
+/// any limits.
 ///
 /// # Errors
 /// This function will error if the file can't be opened and read, if the file
@@ -182,36 +1010,559 @@ pub fn format_file(file : &str, new_lines : Option<bool>, out : Option<String>)
 /// file
 pub fn run_file(
     file : &str,
-    token_limit : Option<usize>,
-    stack_limit : Option<usize>,
+    separators : &[char],
+    runtime : &mut Slug,
 ) -> Result<i64, ExecutionError> {
-    match File::open(file) {
-        Ok(mut data) => {
-            println!("Running {file}");
-            let mut buf = String::new();
-            data.read_to_string(&mut buf)?;
+    let program = load_file(file, separators)?;
+    runtime.tokens = program.tokens;
+    runtime.data = program.data;
+
+    match runtime.execute() {
+        Ok(Some(res)) => Ok(res),
+        Err(err) => Err(ExecutionError::RuntimeError(err)),
+        _ => unreachable!(),
+    }
+}
+
+/// Runs files in order on one runtime, keeping the stack between them.
+///
+/// The last file's result is returned, and an `exit` in an earlier file leaves
+/// its value on the stack for the next one. Limits and counts carry across
+/// every file.
+///
+/// # Errors
+/// This function will error if any file can't be read, is syntaxtically
+/// invalid, or errors during execution
+pub fn run_pipeline(files : &[String], runtime : &mut Slug) -> Result<i64, ExecutionError> {
+    let mut out = None;
+    for (idx, file) in files.iter().enumerate() {
+        let program = load_file(file, &[])?;
+        runtime.tokens = program.tokens;
+        runtime.data = program.data;
+        runtime.ptr = 0;
+        // A stage that exits passes its value on rather than ending the run
+        runtime.halted = false;
+        // Only the last file finishes the run, the others stop when they
+        // reach their end with the stack intact
+        runtime.eof = idx + 1 == files.len();
+
+        if let Some(res) = out.take() {
+            runtime.stack.push(res);
+        }
+        out = runtime.execute()?;
+    }
+
+    out.ok_or(ExecutionError::RuntimeError(RuntimeError::NoOut))
+}
+
+/// Runs a file and then carries on with tokens read from stdin a line at a
+/// time, until the program exits or the input ends.
+///
+/// After each line the top of the stack is printed, or `(empty)` if there's
+/// nothing on it yet. Errors part way through are reported and the offending
+/// line is rolled back.
+///
+/// # Errors
+/// This function will error if the file or stdin can't be read, if the file
+/// is syntaxtically invalid, or if the runtime errors once the input has
+/// ended
+pub fn run_interactive(
+    file : &str,
+    separators : &[char],
+    runtime : &mut Slug,
+) -> Result<i64, ExecutionError> {
+    let program = load_file(file, separators)?;
+    interact(program, separators, runtime, stdin().lock(), io::stdout())
+}
 
-            let tokens = Tokenizer::parse_text(&buf)?;
+/// Runs a program and then carries on with lines read from `input`, like
+/// `run_interactive`, writing the top of the stack after each line to `out`
+///
+/// # Errors
+/// See `run_interactive`
+pub fn interact(
+    program : Program,
+    separators : &[char],
+    runtime : &mut Slug,
+    mut input : impl BufRead,
+    mut out : impl Write,
+) -> Result<i64, ExecutionError> {
+    runtime.data = program.data;
+    runtime.eof = false;
 
+    let mut batch = program.tokens;
+    loop {
+        match runtime.execute_tokens(batch) {
+            Ok(Some(res)) => return Ok(res),
+            Ok(None) => {
+                match runtime.stack.last() {
+                    Some(top) => writeln!(out, "{top}")?,
+                    None => writeln!(out, "(empty)")?,
+                }
+            },
+            Err(err) if runtime.eof => return Err(ExecutionError::RuntimeError(err)),
+            Err(err) => {
+                eprintln!("{err}");
+                runtime.rollback();
+            },
+        }
+
+        let mut buf = String::new();
+        if input.read_line(&mut buf)? == 0 {
+            runtime.eof = true;
+        }
+
+        batch = match Tokenizer::parse_program(&buf, separators) {
+            Ok(program) => program.tokens,
+            Err(errs) => {
+                for err in errs {
+                    eprintln!("{err}");
+                }
+                Vec::new()
+            },
+        };
+    }
+}
+
+/// The outcome of benchmarking a program
+pub struct BenchReport {
+    /// Total tokens executed across every iteration
+    pub tokens :  usize,
+    pub elapsed : Duration,
+}
+
+impl BenchReport {
+    /// Tokens executed per second
+    #[must_use]
+    pub fn throughput(&self) -> f64 {
+        #[expect(
+            clippy::cast_precision_loss,
+            reason = "Throughput is only reported to the user, so precision past 2^52 tokens doesn't matter"
+        )]
+        let tokens = self.tokens as f64;
+        tokens / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Runs a file `iterations` times, discarding any output the program writes
+///
+/// # Errors
+/// This function will error if the file can't be loaded or if any run errors
+pub fn bench_file(file : &str, iterations : u64) -> Result<BenchReport, ExecutionError> {
+    let program = load_file(file, &[])?;
+    let mut runtime = Slug {
+        tokens : program.tokens,
+        data : program.data,
+        eof : true,
+        out : Box::new(io::sink()),
+        ..Slug::new()
+    };
+
+    let mut tokens = 0;
+    let start = Instant::now();
+    for _ in 0 .. iterations {
+        runtime.reset();
+        runtime.execute()?;
+        tokens += runtime.tokens_consumed;
+    }
+
+    Ok(BenchReport {
+        tokens,
+        elapsed : start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Mutex,
+        PoisonError,
+    };
+    use std::{
+        env,
+        iter,
+    };
+
+    use clap::error::ErrorKind as ClapErrorKind;
+
+    use super::*;
+
+    /// Held while parsing arguments or changing the environment, since
+    /// arguments can be read from environment variables
+    static ENV_LOCK : Mutex<()> = Mutex::new(());
+
+    /// Parses command line arguments, without the program name
+    fn parse_cli(args : &[&str]) -> Result<Cli, clap::Error> {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        Cli::try_parse_from(iter::once("slug").chain(args.iter().copied()))
+    }
+
+    /// Writes `contents` to a file in the temporary directory, returning its
+    /// path. `name` should be unique to the test.
+    fn temp_file(name : &str, contents : &str) -> String {
+        let path = env::temp_dir().join(format!("slug-test-{name}"));
+        fs::write(&path, contents).expect("The temporary directory should be writable");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn bench_reports_throughput() {
+        let file = temp_file("bench.slug", "2 3 add 4 mul");
+        let report = bench_file(&file, 10).expect("The program should run");
+        assert_eq!(report.tokens, 50);
+        assert!(report.throughput() > 0.0);
+    }
+
+    #[test]
+    fn bench_rejects_zero_iterations() {
+        let err = parse_cli(&["bench", "--iterations", "0", "x.slug"])
+            .expect_err("Zero iterations should be rejected");
+        assert_eq!(err.kind(), ClapErrorKind::ValueValidation);
+        parse_cli(&["bench", "--iterations", "1", "x.slug"])
+            .expect("One iteration should be accepted");
+    }
+
+    /// Runs a program to completion, returning the runtime and its result
+    fn run_text(text : &str) -> (Slug, i64) {
+        let mut runtime = Slug {
+            tokens : Tokenizer::parse_text(text).expect("Test programs should parse"),
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        let out = runtime
+            .execute()
+            .expect("Test programs should run")
+            .expect("Test programs should return a value");
+        (runtime, out)
+    }
+
+    #[test]
+    fn all_prints_every_value() {
+        let (mut runtime, out) = run_text("1 2 3");
+        assert_eq!(
+            format_result(&mut runtime, out, true, OutputFormat::Text),
+            "1\n2\n3\n"
+        );
+
+        let (mut runtime, out) = run_text("1 2 3");
+        assert_eq!(
+            format_result(&mut runtime, out, false, OutputFormat::Text),
+            "Result: 3\n"
+        );
+    }
+
+    #[test]
+    fn json_result() {
+        let (mut runtime, out) = run_text("2 3 add");
+        assert_eq!(
+            format_result(&mut runtime, out, false, OutputFormat::Json),
+            "{ \"result\": 5, \"tokens\": 3, \"peak_stack\": 2 }\n"
+        );
+
+        // The exiting token is counted too
+        let (mut runtime, out) = run_text("1 exit");
+        assert_eq!(
+            format_result(&mut runtime, out, false, OutputFormat::Json),
+            "{ \"result\": 1, \"tokens\": 2, \"peak_stack\": 1 }\n"
+        );
+    }
+
+    /// Parses the arguments of `slug run`
+    fn run_args(args : &[&str]) -> RunArgs {
+        let cli = parse_cli(&[&["run"], args].concat()).expect("Test arguments should parse");
+        match cli.command {
+            Subcommand::Run(args) => args,
+            command => panic!("Expected a run command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn run_once_returns_result() {
+        let file = temp_file("run-once.slug", "2 3 add");
+        assert_eq!(run_once(&run_args(&[&file])).ok(), Some(5));
+
+        let file = temp_file("run-once-error.slug", "add");
+        assert!(matches!(
+            run_once(&run_args(&[&file])),
+            Err(ExecutionError::RuntimeError(RuntimeError::UnderRead(0)))
+        ));
+    }
+
+    #[test]
+    fn dump_keeps_operands() {
+        // Gives the stack `--dump-on-error` prints when `text` errors
+        let dumped = |name : &str, text : &str| {
+            let file = temp_file(name, text);
+            let args = run_args(&["--dump-on-error", &file]);
             let mut runtime = Slug {
-                stack : Vec::new(),
-                stack_limit,
-                tokens,
-                ptr : 0,
-                token_limit,
-                tokens_consumed : 0,
-                eof : true,
+                out : Box::new(io::sink()),
+                ..args.runtime()
             };
+            run_file(&file, &args.separators(), &mut runtime)
+                .expect_err("The program should error");
+            runtime.describe()
+        };
+        assert_eq!(
+            dumped("dump-mean.slug", "5 9223372036854775807 1 mean"),
+            "0 | 5\n1 | 9223372036854775807\n2 | 1\n"
+        );
+        assert_eq!(
+            dumped("dump-eval.slug", "7 8 -1 1 eval"),
+            "0 | 7\n1 | 8\n2 | -1\n3 | 1\n"
+        );
+        assert_eq!(dumped("dump-div.slug", "4 0 div"), "0 | 4\n1 | 0\n");
+    }
+
+    /// Parses the arguments of `slug fmt`
+    fn fmt_args(args : &[&str]) -> FmtArgs {
+        let cli =
+            parse_cli(&[&["fmt", "x.slug"], args].concat()).expect("Test arguments should parse");
+        match cli.command {
+            Subcommand::Fmt(args) => args,
+            command => panic!("Expected a fmt command, got {command:?}"),
+        }
+    }
 
-            let output = runtime.execute();
+    #[test]
+    fn preserve_blank_lines() {
+        let args = fmt_args(&["--preserve-blank-lines", "-n", "false"]);
+        assert_eq!(
+            format_text("1 2\nadd\n\n\n3   mul\n\n", &args).expect("Should parse"),
+            "1 2 add\n\n3 mul\n"
+        );
+        // A data section can span a blank line, it's kept with the other data
+        assert_eq!(
+            format_text("data 1 2\n\n3 end\n0 dataload\n\n1 add", &args).expect("Should parse"),
+            "data 1 2 3 end 0 dataload\n\n1 add\n"
+        );
+    }
+
+    #[test]
+    fn limits_from_environment() {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        // SAFETY: Every test that reads the environment holds `ENV_LOCK`
+        unsafe { env::set_var("SLUG_TOKEN_LIMIT", "12") };
+        let from_env = Cli::try_parse_from(["slug", "run", "x.slug"]);
+        let from_flag = Cli::try_parse_from(["slug", "run", "-t", "34", "x.slug"]);
+        // SAFETY: As above
+        unsafe { env::remove_var("SLUG_TOKEN_LIMIT") };
 
-            match output {
-                Ok(Some(res)) => Ok(res),
-                Err(err) => Err(ExecutionError::RuntimeError(err)),
-                _ => unreachable!(),
+        let token_limit = |cli : Result<Cli, clap::Error>| {
+            match cli.expect("Test arguments should parse").command {
+                Subcommand::Run(args) => args.token_limit,
+                command => panic!("Expected a run command, got {command:?}"),
             }
-        },
+        };
+        assert_eq!(token_limit(from_env), Some(12));
+        assert_eq!(token_limit(from_flag), Some(34));
+    }
+
+    #[test]
+    fn interactive_prints_each_top() {
+        let program = Tokenizer::parse_program("1", &[]).expect("Test programs should parse");
+        let mut runtime = Slug::new();
+        let mut out = Vec::new();
+        let res = interact(program, &[], &mut runtime, &b"2 add\n3 mul\n"[..], &mut out);
+        assert_eq!(res.expect("Test programs should run"), 9);
+        assert_eq!(String::from_utf8_lossy(&out), "1\n3\n9\n");
+    }
+
+    #[test]
+    fn shebang() {
+        let file = temp_file("shebang.slug", "#!/usr/bin/env slug run\n2 3 add\n");
+        let mut runtime = Slug {
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        let out = run_file(&file, &[], &mut runtime).expect("The program should run");
+        assert_eq!(out, 5);
+
+        let file = temp_file("late-shebang.slug", "2 3 add\n#!/usr/bin/env slug run\n");
+        let err = load_file(&file, &[]).expect_err("Only a leading shebang is skipped");
+        let ExecutionError::ParseTextError(errs) = err else {
+            panic!("Expected a parse error, got {err:?}");
+        };
+        assert_eq!(errs[0].idx, 3);
+    }
+
+    #[test]
+    fn repl_prompt_shows_depth() {
+        let mut runtime = Slug::new();
+        assert_eq!(repl_prompt("[{depth}] > ", &runtime), "[0] > ");
+        runtime.stack = vec![4, 5, 6];
+        assert_eq!(repl_prompt("[{depth}] > ", &runtime), "[3] > ");
+        assert_eq!(repl_prompt("> ", &runtime), "> ");
+    }
+
+    #[test]
+    fn pipeline() {
+        let first = temp_file("pipeline-first.slug", "2 3 add");
+        let second = temp_file("pipeline-second.slug", "4 mul");
+        let exits = temp_file("pipeline-exits.slug", "9 2 3 add exit");
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        let out = run_pipeline(&[first, second.clone()], &mut runtime);
+        assert_eq!(out.expect("The pipeline should run"), 20);
+
+        // A stage that exits carries on to the next with its value pushed back
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        let out = run_pipeline(&[exits, second], &mut runtime);
+        assert_eq!(out.expect("The pipeline should run"), 20);
+        assert_eq!(runtime.stack, vec![9]);
+    }
+
+    #[test]
+    fn selftest_passes() {
+        assert_eq!(selftest(), ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn trace_is_json() {
+        let mut runtime = Slug {
+            trace : Some(Vec::new()),
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens =
+            Tokenizer::parse_text("3 goto 7 2 5 add").expect("Test programs should parse");
+        runtime.execute().expect("Test programs should run");
+        let trace = runtime.trace.expect("Tokens should be traced");
+
+        let json : serde_json::Value =
+            serde_json::from_str(&trace_json(&trace)).expect("The trace should be JSON");
+        let events = json.as_array().expect("The trace should be an array");
+        let names : Vec<&str> = events
+            .iter()
+            .filter_map(|event| event["name"].as_str())
+            .collect();
+        assert_eq!(names, ["3", "goto", "2", "5", "add"]);
+        let indices : Vec<i64> = events
+            .iter()
+            .filter_map(|event| event["args"]["index"].as_i64())
+            .collect();
+        assert_eq!(indices, [0, 1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn indent_loops() {
+        let args = fmt_args(&["--indent-loops"]);
+        assert_eq!(
+            format_text("5 1 sub dup -6 swap hopif", &args).expect("Should parse"),
+            "5\n  1\n  sub\n  dup\n  -6\n  swap\n  hopif\n"
+        );
+        // A hop too far to land anywhere isn't a loop
+        assert_eq!(
+            format_text("1 9223372036854775807 hop", &args).expect("Should parse"),
+            "1\n9223372036854775807\nhop\n"
+        );
+    }
+
+    #[test]
+    fn run_all_checks_each_program() {
+        let dir = env::temp_dir().join("slug-test-run-all");
+        fs::create_dir_all(&dir).expect("The temporary directory should be writable");
+        let write = |name : &str, contents : &str| {
+            fs::write(dir.join(name), contents)
+                .expect("The temporary directory should be writable");
+        };
+        write("sum.slug", "2 3 add");
+        write("sum.expected", "5\n");
+        write("product.slug", "2 3 mul");
+        write("product.expected", "6\n");
+        let dir_name = dir.to_string_lossy();
+        let code = run_all(&dir_name, "expected", None).expect("The directory should be readable");
+        assert_eq!(code, ExitCode::SUCCESS);
+
+        write("product.expected", "7\n");
+        let code = run_all(&dir_name, "expected", None).expect("The directory should be readable");
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_file_checks_expected() {
+        let file = temp_file("expect.slug", "2 3 add");
+        assert_eq!(test_file(&file, 5, None), ExitCode::SUCCESS);
+        assert_eq!(test_file(&file, 6, None), ExitCode::FAILURE);
+
+        let mut out = Vec::new();
+        assert!(check_file(&file, 5, None, &mut out));
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            format!("ok: {file} returned 5\n")
+        );
+
+        let mut out = Vec::new();
+        assert!(!check_file(&file, 6, None, &mut out));
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            format!("FAILED: {file} returned 5, expected 6\n")
+        );
+
+        let file = temp_file("expect-error.slug", "add");
+        let mut out = Vec::new();
+        assert!(!check_file(&file, 5, None, &mut out));
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            format!(
+                "FAILED: {file} errored with {}\n",
+                RuntimeError::UnderRead(0)
+            )
+        );
+    }
+
+    /// Checks that formatting `text` gives text that parses to the same tokens
+    fn assert_round_trips(text : &str) {
+        let formatted = format_text(text, &fmt_args(&[])).expect("Test programs should parse");
+        assert_eq!(
+            Tokenizer::parse_text(&formatted).expect("Formatted programs should parse"),
+            Tokenizer::parse_text(text).expect("Test programs should parse"),
+        );
+    }
+
+    #[test]
+    fn dup_round_trips() {
+        assert_round_trips("3 dup mul");
+    }
+
+    #[test]
+    fn memory_image() {
+        let file = temp_file("memory.txt", "4 5\n6\n");
+        let memory = load_memory(&file).expect("The memory image should load");
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::with_memory(memory)
+        };
+        runtime.tokens = Tokenizer::parse_text("fillback").expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        assert_eq!(runtime.stack, vec![4, 5, 6]);
+
+        let file = temp_file("bad-memory.txt", "4 five");
+        let err = load_memory(&file).expect_err("`five` isn't an integer");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn mod_round_trips() {
+        assert_round_trips("17 5 mod");
+    }
 
-        Err(err) => Err(ExecutionError::IoError(err)),
+    #[test]
+    fn sandbox_rejects_now() {
+        let file = temp_file("sandbox.slug", "1 now");
+        run_once(&run_args(&[&file])).expect("now should run outside a sandbox");
+        assert!(matches!(
+            run_once(&run_args(&["--sandbox", &file])),
+            Err(ExecutionError::RuntimeError(
+                RuntimeError::NonDeterministic(1)
+            ))
+        ));
     }
 }
@@ -2,16 +2,20 @@ use std::fmt::{
     self,
     Display,
     Formatter,
+    Write,
 };
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Opp {
     Add,
+    /// Pops `rhs` then `lhs` and pushes `lhs - rhs`, so `5 3 sub` is `2`. The
+    /// order is flipped when the runtime reverses operands.
     Sub,
     Mul,
-    /// Note: Pushes 2 values, the output and the remainder
+    /// Note: Pushes 2 values, the output and the remainder. Operands are
+    /// popped in the same order as `sub`.
     Div,
     /// Dump the stack into the output
     Dump,
@@ -23,12 +27,477 @@ pub enum Opp {
     Drop,
     /// Hops some amount of tokens fowards or backwards
     Hop,
-    /// Push the position of the pointer onto the stack
+    /// Push the position of the pointer onto the stack. This is the 0 indexed
+    /// position of the `pos` token itself, so `5 6 pos` pushes `2` and
+    /// `pos goto` jumps back to the `pos`.
     Pos,
     /// Exits the program
     Exit,
+    /// Pops an index and jumps to it, so the token at that index, counting
+    /// from 0, runs next
     Goto,
     Flip,
+    /// Pushes -1, 0, or 1 depending on the sign of the top value
+    Sign,
+    /// Pops a value and pushes how many times it appears in the rest of the
+    /// stack
+    CountOf,
+    /// Pops a condition and an offset, hopping by the offset if the condition
+    /// is nonzero
+    HopIf,
+    /// Pushes the milliseconds elapsed since the runtime was created. This is
+    /// the only non-deterministic opperation, so it errors in a sandbox.
+    Now,
+    /// Pops an expected value and errors if the value below it differs. Only
+    /// the expected value is consumed.
+    AssertEq,
+    /// Pops a value and pushes the index of its first occurrence from the
+    /// bottom of the stack, or -1 if it's absent
+    IndexOf,
+    /// Pops an index and pushes that value from the program's data sections
+    DataLoad,
+    /// Pops two indices, counted from the bottom of the stack, and swaps the
+    /// values at them
+    SwapAt,
+    /// Pops two values and pushes the distance between them. Distances past
+    /// `i64::MAX`, only possible with operands of opposite signs, saturate.
+    AbsDiff,
+    /// Pushes the product of the top two values, leaving them in place
+    MulPeek,
+    /// Pushes the 64 bit FNV-1a hash of the little endian bytes of every value
+    /// on the stack, bottom to top, leaving the stack in place
+    Hash,
+    /// Pops an opperation's discriminant and pushes how many times it appears
+    /// in the loaded program
+    OpCount,
+    /// Pops a non-negative value and pushes its decimal digits, most
+    /// significant first, followed by how many digits there were
+    Digits,
+    /// Pops a count and then that many codes, runs them as a program in a child
+    /// runtime, and pushes its result. See `Token::from_code` for the encoding.
+    Eval,
+    /// Pops a max, a min, and a value, and pushes the value clamped into
+    /// `[min, max]`
+    Clamp,
+    /// Pops a value and pushes the largest value `runmax` has seen so far
+    RunningMax,
+    /// Pops a count and rotates the whole stack by it, with positive counts
+    /// moving values toward the top and the top wrapping to the bottom
+    RotateN,
+    /// Undoes `div`: pops a divisor, a quotient, and a remainder and pushes
+    /// `quotient * divisor + remainder`, so `17 5 div 5 recombine` pushes `17`
+    Recombine,
+    /// Pushes 1 if both of the top two values are nonzero, otherwise 0
+    LogAnd,
+    /// Pushes 1 if either of the top two values is nonzero, otherwise 0
+    LogOr,
+    /// Squares the top value
+    Square,
+    /// Cubes the top value
+    Cube,
+    /// Replaces the whole stack with how many values were on it
+    CountClear,
+    /// Pops a register index and a value, storing the value in that register
+    Set,
+    /// Pops a register index and pushes the value stored in that register
+    Get,
+    /// Like `div` but rounds the quotient down rather than toward zero, so the
+    /// remainder takes the sign of the divisor. `-7 2 fdiv` pushes `1` then
+    /// `-4` where `div` pushes `-1` then `-3`. Operands are popped in the
+    /// same order as `sub`.
+    FloorDiv,
+    /// Does nothing, useful for padding a program or reserving a jump target
+    Nop,
+    /// Pops a max, a min, and a value, pushing `1` if the value is within
+    /// `[min, max]` and `0` otherwise
+    InRange,
+    /// Pops a condition and a value, pushing the value negated if the condition
+    /// is nonzero and unchanged otherwise
+    NegIf,
+    /// Pops every value on the stack and pushes their mean, truncated toward
+    /// zero
+    Mean,
+    /// Reads a line from the input and pushes its length in bytes, not counting
+    /// the line ending. Pushes `-1` at the end of the input.
+    ReadLineLen,
+    /// Pops a value and pushes how many of its bits are set, counting the two's
+    /// complement bits of negative values
+    PopCount,
+    /// Pops a count `n` and reverses the order of the top `n` values
+    ReverseN,
+    /// Pops `n` and pushes `n!`
+    Fact,
+    /// Pops a count `n` and a value, and pushes the value `n` times
+    Fill,
+    /// Pops a value and pushes `1` if it's prime, `0` otherwise
+    IsPrime,
+    /// Pops a non-negative value and pushes the sum of its decimal digits.
+    /// Negative values error with `RuntimeError::NegativeOperand`.
+    DigitSum,
+    /// Pops a condition and duplicates the top value if it's nonzero
+    DupIf,
+    /// Moves the whole stack to the end of memory, bottom first, leaving the
+    /// stack empty
+    Spill,
+    /// Moves all of memory onto the stack, pushing from address `0` up and
+    /// leaving memory empty, so `spill fillback` leaves the stack as it was
+    Fillback,
+    /// Pops `n` and pushes the `n`th Fibonacci number, where `0 fib` is `0` and
+    /// `1 fib` is `1`
+    Fib,
+    /// Errors with `RuntimeError::AssertionFailed` if anything is left on the
+    /// stack
+    AssertEmpty,
+    /// Pops a positive value and pushes the largest power of two that isn't
+    /// above it
+    FloorPow2,
+    /// Pushes `1` if more tokens may still be fed in, as in a Repl, and `0` if
+    /// the whole program is already loaded
+    IsStreaming,
+    /// Pushes how many distinct values are on the stack, leaving them in place
+    DistinctCount,
+    /// Pushes how many tokens the loaded program has
+    Len,
+    /// Pops a condition and exits the program if it's nonzero, returning the
+    /// top value like `exit`
+    ExitIf,
+    /// Pops an index and a value and pushes the byte at that index, from `0` to
+    /// `7`, of the value's little endian two's complement representation
+    Byte,
+    /// Swaps the bottom half of the stack with the top half, leaving the middle
+    /// value in place when there's an odd number of values
+    SwapHalves,
+    /// Pushes a copy of the top value
+    Dup,
+    /// Pops a positive value and pushes the floor of its base 2 logarithm, so
+    /// `8` and `15` both give `3`
+    Log2,
+    /// Pops two values and pushes `1` if they are equal, `0` otherwise
+    Eq,
+    /// Pops two values and pushes `1` if the lower is less than the top, `0`
+    /// otherwise, so `2 5 lt` is `1`. Operands are popped in the same order as
+    /// `sub`.
+    Lt,
+    /// Pops two values and pushes `1` if the lower is greater than the top, `0`
+    /// otherwise, so `5 2 gt` is `1`. Operands are popped in the same order as
+    /// `sub`.
+    Gt,
+    /// Pops a target and then a condition, and when the condition is `0` jumps
+    /// so the token at the target, counting from 0, runs next
+    Jz,
+    /// Pops an amount and a value and pushes the value's bits rotated left by
+    /// the amount modulo 64, with bits wrapping around
+    Rol,
+    /// Pops an amount and a value and pushes the value's bits rotated right by
+    /// the amount modulo 64, with bits wrapping around
+    Ror,
+    /// Pops a value and pushes its negation
+    Neg,
+    /// Pops every value on the stack and pushes their median, the lower of the
+    /// two middle values when there are an even number
+    Median,
+    /// Pops two values and pushes the remainder of dividing the lower by the
+    /// top, with the sign of the lower, so `17 5 mod` is `2`. Operands are
+    /// popped in the same order as `sub`.
+    Mod,
+    /// Reads the next whitespace separated integer from the input and pushes it
+    In,
+    /// Pops a value and writes it to the output as a Unicode character, without
+    /// a newline, so `72 emit` writes `H`
+    Emit,
+}
+
+impl Opp {
+    /// Every opperation in discriminant order, so `Opp::all()[op as usize]` is
+    /// `op`
+    #[must_use]
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Add,
+            Self::Sub,
+            Self::Mul,
+            Self::Div,
+            Self::Dump,
+            Self::Top,
+            Self::Swap,
+            Self::Drop,
+            Self::Hop,
+            Self::Pos,
+            Self::Exit,
+            Self::Goto,
+            Self::Flip,
+            Self::Sign,
+            Self::CountOf,
+            Self::HopIf,
+            Self::Now,
+            Self::AssertEq,
+            Self::IndexOf,
+            Self::DataLoad,
+            Self::SwapAt,
+            Self::AbsDiff,
+            Self::MulPeek,
+            Self::Hash,
+            Self::OpCount,
+            Self::Digits,
+            Self::Eval,
+            Self::Clamp,
+            Self::RunningMax,
+            Self::RotateN,
+            Self::Recombine,
+            Self::LogAnd,
+            Self::LogOr,
+            Self::Square,
+            Self::Cube,
+            Self::CountClear,
+            Self::Set,
+            Self::Get,
+            Self::FloorDiv,
+            Self::Nop,
+            Self::InRange,
+            Self::NegIf,
+            Self::Mean,
+            Self::ReadLineLen,
+            Self::PopCount,
+            Self::ReverseN,
+            Self::Fact,
+            Self::Fill,
+            Self::IsPrime,
+            Self::DigitSum,
+            Self::DupIf,
+            Self::Spill,
+            Self::Fillback,
+            Self::Fib,
+            Self::AssertEmpty,
+            Self::FloorPow2,
+            Self::IsStreaming,
+            Self::DistinctCount,
+            Self::Len,
+            Self::ExitIf,
+            Self::Byte,
+            Self::SwapHalves,
+            Self::Dup,
+            Self::Log2,
+            Self::Eq,
+            Self::Lt,
+            Self::Gt,
+            Self::Jz,
+            Self::Rol,
+            Self::Ror,
+            Self::Neg,
+            Self::Median,
+            Self::Mod,
+            Self::In,
+            Self::Emit,
+        ]
+    }
+
+    /// A short description of what the opperation does
+    #[must_use]
+    pub const fn help(self) -> &'static str {
+        match self {
+            Self::Add => "Adds the top two values",
+            Self::Sub => "Subtracts the top value from the one below it",
+            Self::Mul => "Multiplies the top two values",
+            Self::Div => {
+                "Divides the second value by the top, pushing the remainder then the quotient"
+            },
+            Self::Dump => "Prints every value on the stack, bottom to top",
+            Self::Top => "Prints the top value",
+            Self::Swap => "Swaps the top two values",
+            Self::Drop => "Drops the top value",
+            Self::Hop => "Moves the pointer forwards or backwards by the top value",
+            Self::Pos => "Pushes the index of this token",
+            Self::Exit => "Exits the program with the top value as the result",
+            Self::Goto => "Jumps to the token at the index on top of the stack",
+            Self::Flip => "Swaps the top value with the bottom value",
+            Self::Sign => "Pushes -1, 0, or 1 for a negative, zero, or positive value",
+            Self::CountOf => "Pushes how many times a value appears in the rest of the stack",
+            Self::HopIf => "Hops by an offset if a condition is nonzero",
+            Self::Now => "Pushes the milliseconds since the runtime started",
+            Self::AssertEq => "Errors if the value below the top doesn't equal the top",
+            Self::IndexOf => "Pushes the index of a value's first occurrence, or -1",
+            Self::DataLoad => "Pushes the value at an index of the data sections",
+            Self::SwapAt => "Swaps the values at two indices of the stack",
+            Self::AbsDiff => "Pushes the distance between the top two values",
+            Self::MulPeek => "Pushes the product of the top two values, keeping them",
+            Self::Hash => "Pushes the FNV-1a hash of the whole stack",
+            Self::OpCount => "Pushes how many times an opperation appears in the program",
+            Self::Digits => "Pushes the decimal digits of a value then the digit count",
+            Self::Eval => "Runs n codes from the stack as a program and pushes its result",
+            Self::Clamp => "Clamps a value into a range",
+            Self::RunningMax => "Pushes the largest value runmax has seen",
+            Self::RotateN => "Rotates the whole stack by n positions toward the top",
+            Self::Recombine => "Pushes quotient * divisor + remainder",
+            Self::LogAnd => "Pushes 1 if both of the top two values are nonzero, otherwise 0",
+            Self::LogOr => "Pushes 1 if either of the top two values is nonzero, otherwise 0",
+            Self::Square => "Squares the top value",
+            Self::Cube => "Cubes the top value",
+            Self::CountClear => "Replaces the whole stack with how many values were on it",
+            Self::Set => "Stores a value in one of the 26 registers",
+            Self::Get => "Pushes the value stored in one of the 26 registers",
+            Self::FloorDiv => "Divides rounding down, pushing the remainder then the quotient",
+            Self::Nop => "Does nothing",
+            Self::InRange => "Pushes whether a value is within a range",
+            Self::NegIf => "Negates a value if the condition is nonzero",
+            Self::Mean => "Replaces the stack with the mean of its values",
+            Self::ReadLineLen => "Reads a line of input and pushes its length, or -1 at the end",
+            Self::PopCount => "Pushes the number of set bits in a value",
+            Self::ReverseN => "Reverses the top n values",
+            Self::Fact => "Pushes the factorial of a value",
+            Self::Fill => "Pushes a value n times",
+            Self::IsPrime => "Pushes whether a value is prime",
+            Self::DigitSum => "Pushes the sum of a value's digits",
+            Self::DupIf => "Duplicates the top value if the condition is nonzero",
+            Self::Spill => "Moves the whole stack into memory",
+            Self::Fillback => "Moves all of memory onto the stack",
+            Self::Fib => "Pushes the nth Fibonacci number",
+            Self::AssertEmpty => "Asserts the stack is empty",
+            Self::FloorPow2 => "Pushes the largest power of two not above a value",
+            Self::IsStreaming => "Pushes whether more input may still be fed in",
+            Self::DistinctCount => "Pushes how many distinct values are on the stack",
+            Self::Len => "Pushes the number of tokens in the program",
+            Self::ExitIf => "Exits the program if the condition is nonzero",
+            Self::Byte => "Pushes a byte of a value",
+            Self::SwapHalves => "Swaps the bottom and top halves of the stack",
+            Self::Dup => "Duplicates the top value",
+            Self::Log2 => "Pushes the integer base 2 logarithm of a value",
+            Self::Eq => "Pushes whether two values are equal",
+            Self::Lt => "Pushes whether a value is less than another",
+            Self::Gt => "Pushes whether a value is greater than another",
+            Self::Jz => "Jumps to a token if a condition is zero",
+            Self::Rol => "Rotates a value's bits left",
+            Self::Ror => "Rotates a value's bits right",
+            Self::Neg => "Negates a value",
+            Self::Median => "Replaces the stack with the median of its values",
+            Self::Mod => "Pushes the remainder of a division",
+            Self::In => "Reads an integer from the input",
+            Self::Emit => "Writes a value as a character",
+        }
+    }
+
+    /// The opperation's effect on the stack, as `before -- after` with the top
+    /// of the stack on the right
+    #[must_use]
+    #[expect(
+        clippy::match_same_arms,
+        reason = "Each opperation reads best on its own line"
+    )]
+    pub const fn stack_effect(self) -> &'static str {
+        match self {
+            Self::Add => "a b -- a+b",
+            Self::Sub => "a b -- a-b",
+            Self::Mul => "a b -- a*b",
+            Self::Div => "a b -- a%b a/b",
+            Self::Dump => "--",
+            Self::Top => "a -- a",
+            Self::Swap => "a b -- b a",
+            Self::Drop => "a --",
+            Self::Hop => "n --",
+            Self::Pos => "-- i",
+            Self::Exit => "a --",
+            Self::Goto => "i --",
+            Self::Flip => "a ... b -- b ... a",
+            Self::Sign => "a -- s",
+            Self::CountOf => "x -- n",
+            Self::HopIf => "n c --",
+            Self::Now => "-- ms",
+            Self::AssertEq => "a e -- a",
+            Self::IndexOf => "x -- i",
+            Self::DataLoad => "i -- v",
+            Self::SwapAt => "i j --",
+            Self::AbsDiff => "a b -- |a-b|",
+            Self::MulPeek => "a b -- a b a*b",
+            Self::Hash => "-- h",
+            Self::OpCount => "d -- n",
+            Self::Digits => "a -- d... n",
+            Self::Eval => "c... n -- r",
+            Self::Clamp => "v min max -- v'",
+            Self::RunningMax => "a -- max",
+            Self::RotateN => "... n -- ...",
+            Self::Recombine => "r q d -- a",
+            Self::LogAnd => "a b -- a&&b",
+            Self::LogOr => "a b -- a||b",
+            Self::Square => "a -- a*a",
+            Self::Cube => "a -- a*a*a",
+            Self::CountClear => "... -- n",
+            Self::Set => "v r --",
+            Self::Get => "r -- v",
+            Self::FloorDiv => "a b -- a%b a/b",
+            Self::Nop => "--",
+            Self::InRange => "v min max -- b",
+            Self::NegIf => "a c -- a'",
+            Self::Mean => "a... -- mean",
+            Self::ReadLineLen => "-- n",
+            Self::PopCount => "a -- n",
+            Self::ReverseN => "a... n -- a...",
+            Self::Fact => "n -- n!",
+            Self::Fill => "a n -- a...",
+            Self::IsPrime => "a -- b",
+            Self::DigitSum => "a -- s",
+            Self::DupIf => "a c -- a a?",
+            Self::Spill => "a... --",
+            Self::Fillback => "-- a...",
+            Self::Fib => "n -- f",
+            Self::AssertEmpty => "--",
+            Self::FloorPow2 => "a -- p",
+            Self::IsStreaming => "-- b",
+            Self::DistinctCount => "a... -- a... n",
+            Self::Len => "-- n",
+            Self::ExitIf => "a c -- a",
+            Self::Byte => "a i -- b",
+            Self::SwapHalves => "a... b... -- b... a...",
+            Self::Dup => "a -- a a",
+            Self::Log2 => "a -- l",
+            Self::Eq => "a b -- c",
+            Self::Lt => "a b -- c",
+            Self::Gt => "a b -- c",
+            Self::Jz => "c n --",
+            Self::Rol => "a n -- b",
+            Self::Ror => "a n -- b",
+            Self::Neg => "a -- b",
+            Self::Median => "a... -- median",
+            Self::Mod => "a b -- r",
+            Self::In => "-- a",
+            Self::Emit => "a --",
+        }
+    }
+}
+
+/// Describes every opperation as a JSON array of objects with its
+/// `mnemonic`, `help`, and `stack_effect`
+#[must_use]
+pub fn instruction_set_json() -> String {
+    let entries : Vec<String> = Opp::all()
+        .iter()
+        .map(|op| {
+            format!(
+                "{{ \"mnemonic\": {}, \"help\": {}, \"stack_effect\": {} }}",
+                json_string(&op.to_string()),
+                json_string(op.help()),
+                json_string(op.stack_effect())
+            )
+        })
+        .collect();
+
+    format!("[\n  {}\n]", entries.join(",\n  "))
+}
+
+/// Quotes and escapes a string for JSON
+#[must_use]
+pub fn json_string(s : &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
 impl FromStr for Opp {
@@ -49,6 +518,68 @@ impl FromStr for Opp {
             "exit" => Ok(Self::Exit),
             "goto" => Ok(Self::Goto),
             "flip" => Ok(Self::Flip),
+            "sign" => Ok(Self::Sign),
+            "countof" => Ok(Self::CountOf),
+            "hopif" => Ok(Self::HopIf),
+            "now" => Ok(Self::Now),
+            "asserteq" => Ok(Self::AssertEq),
+            "indexof" => Ok(Self::IndexOf),
+            "dataload" => Ok(Self::DataLoad),
+            "swapat" => Ok(Self::SwapAt),
+            "absdiff" => Ok(Self::AbsDiff),
+            "mulpeek" => Ok(Self::MulPeek),
+            "hash" => Ok(Self::Hash),
+            "opcount" => Ok(Self::OpCount),
+            "digits" => Ok(Self::Digits),
+            "eval" => Ok(Self::Eval),
+            "clamp" => Ok(Self::Clamp),
+            "runmax" => Ok(Self::RunningMax),
+            "rotaten" => Ok(Self::RotateN),
+            "recombine" => Ok(Self::Recombine),
+            "land" => Ok(Self::LogAnd),
+            "lor" => Ok(Self::LogOr),
+            "sq" => Ok(Self::Square),
+            "cube" => Ok(Self::Cube),
+            "countclear" => Ok(Self::CountClear),
+            "set" => Ok(Self::Set),
+            "get" => Ok(Self::Get),
+            "fdiv" => Ok(Self::FloorDiv),
+            "nop" => Ok(Self::Nop),
+            "inrange" => Ok(Self::InRange),
+            "negif" => Ok(Self::NegIf),
+            "mean" => Ok(Self::Mean),
+            "readlinelen" => Ok(Self::ReadLineLen),
+            "popcount" => Ok(Self::PopCount),
+            "reversen" => Ok(Self::ReverseN),
+            "fact" => Ok(Self::Fact),
+            "fill" => Ok(Self::Fill),
+            "isprime" => Ok(Self::IsPrime),
+            "digitsum" => Ok(Self::DigitSum),
+            "dupif" => Ok(Self::DupIf),
+            "spill" => Ok(Self::Spill),
+            "fillback" => Ok(Self::Fillback),
+            "fib" => Ok(Self::Fib),
+            "assertempty" => Ok(Self::AssertEmpty),
+            "floorpow2" => Ok(Self::FloorPow2),
+            "isstreaming" => Ok(Self::IsStreaming),
+            "distinct" => Ok(Self::DistinctCount),
+            "len" => Ok(Self::Len),
+            "exitif" => Ok(Self::ExitIf),
+            "byte" => Ok(Self::Byte),
+            "swaphalves" => Ok(Self::SwapHalves),
+            "dup" => Ok(Self::Dup),
+            "log2" => Ok(Self::Log2),
+            "eq" => Ok(Self::Eq),
+            "lt" => Ok(Self::Lt),
+            "gt" => Ok(Self::Gt),
+            "jz" => Ok(Self::Jz),
+            "rol" => Ok(Self::Rol),
+            "ror" => Ok(Self::Ror),
+            "neg" => Ok(Self::Neg),
+            "median" => Ok(Self::Median),
+            "mod" => Ok(Self::Mod),
+            "in" => Ok(Self::In),
+            "emit" => Ok(Self::Emit),
             _ => Err(()),
         }
     }
@@ -70,7 +601,96 @@ impl Display for Opp {
             Self::Exit => "exit",
             Self::Goto => "goto",
             Self::Flip => "flip",
+            Self::Sign => "sign",
+            Self::CountOf => "countof",
+            Self::HopIf => "hopif",
+            Self::Now => "now",
+            Self::AssertEq => "asserteq",
+            Self::IndexOf => "indexof",
+            Self::DataLoad => "dataload",
+            Self::SwapAt => "swapat",
+            Self::AbsDiff => "absdiff",
+            Self::MulPeek => "mulpeek",
+            Self::Hash => "hash",
+            Self::OpCount => "opcount",
+            Self::Digits => "digits",
+            Self::Eval => "eval",
+            Self::Clamp => "clamp",
+            Self::RunningMax => "runmax",
+            Self::RotateN => "rotaten",
+            Self::Recombine => "recombine",
+            Self::LogAnd => "land",
+            Self::LogOr => "lor",
+            Self::Square => "sq",
+            Self::Cube => "cube",
+            Self::CountClear => "countclear",
+            Self::Set => "set",
+            Self::Get => "get",
+            Self::FloorDiv => "fdiv",
+            Self::Nop => "nop",
+            Self::InRange => "inrange",
+            Self::NegIf => "negif",
+            Self::Mean => "mean",
+            Self::ReadLineLen => "readlinelen",
+            Self::PopCount => "popcount",
+            Self::ReverseN => "reversen",
+            Self::Fact => "fact",
+            Self::Fill => "fill",
+            Self::IsPrime => "isprime",
+            Self::DigitSum => "digitsum",
+            Self::DupIf => "dupif",
+            Self::Spill => "spill",
+            Self::Fillback => "fillback",
+            Self::Fib => "fib",
+            Self::AssertEmpty => "assertempty",
+            Self::FloorPow2 => "floorpow2",
+            Self::IsStreaming => "isstreaming",
+            Self::DistinctCount => "distinct",
+            Self::Len => "len",
+            Self::ExitIf => "exitif",
+            Self::Byte => "byte",
+            Self::SwapHalves => "swaphalves",
+            Self::Dup => "dup",
+            Self::Log2 => "log2",
+            Self::Eq => "eq",
+            Self::Lt => "lt",
+            Self::Gt => "gt",
+            Self::Jz => "jz",
+            Self::Rol => "rol",
+            Self::Ror => "ror",
+            Self::Neg => "neg",
+            Self::Median => "median",
+            Self::Mod => "mod",
+            Self::In => "in",
+            Self::Emit => "emit",
         };
         write!(f, "{t}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_is_indexed_by_discriminant() {
+        for (idx, op) in Opp::all().iter().enumerate() {
+            assert_eq!(*op as usize, idx, "{op} is out of place in Opp::all");
+        }
+    }
+
+    #[test]
+    fn instruction_set_json_lists_every_opperation() {
+        let json = instruction_set_json();
+        assert_eq!(json.matches("\"mnemonic\"").count(), Opp::all().len());
+        for op in Opp::all() {
+            let mnemonic = format!("\"mnemonic\": {}", json_string(&op.to_string()));
+            assert!(json.contains(&mnemonic), "{op} is missing from the JSON");
+        }
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("a \"b\" \\ \n"), "\"a \\\"b\\\" \\\\ \\u000a\"");
+    }
+}
@@ -1,42 +1,297 @@
-use crate::error::RuntimeError;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::fmt::Write as _;
+use std::io::{
+    self,
+    BufRead,
+    Cursor,
+    Write,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+use std::{
+    iter,
+    mem,
+};
+
+use clap::ValueEnum;
+
+use crate::error::{
+    ParseTextError,
+    RuntimeError,
+};
 use crate::opp::Opp;
-use crate::token::Token;
+use crate::token::{
+    Program,
+    Token,
+    Tokenizer,
+    intern,
+};
+
+/// An opperation added to a runtime with `Slug::register_op`
+pub type CustomOp = Box<dyn Fn(&mut Vec<i64>) -> Result<(), RuntimeError>>;
+
+/// How `add`, `sub`, and `mul` handle results that don't fit in an `i64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ArithMode {
+    /// Wrap around in two's complement
+    Wrapping,
+    /// Error with `RuntimeError::Overflow`
+    #[default]
+    Checked,
+    /// Clamp to `i64::MIN` or `i64::MAX`
+    Saturating,
+}
+
+/// A token executed while tracing, see `Slug::trace`
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEvent {
+    pub token :    Token,
+    /// Where the token is in the program
+    pub index :    i64,
+    /// When the token started executing, measured from when the runtime was
+    /// created
+    pub start :    Duration,
+    pub duration : Duration,
+}
+
+/// The state restored by `Slug::rollback`
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    stack :           Vec<i64>,
+    ptr :             i64,
+    tokens_len :      usize,
+    tokens_consumed : usize,
+}
+
+/// Values the token being executed has popped, so they can be put back if it
+/// errors
+#[derive(Debug, Default)]
+pub struct Operands {
+    /// Values popped from below where the stack started, top first
+    popped : Vec<i64>,
+    /// How deep the stack is with those values popped
+    depth :  usize,
+}
 
 /// A Slug runtime
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "These are independent runtime settings"
+)]
 pub struct Slug {
-    pub stack :           Vec<i64>,
-    pub stack_limit :     Option<usize>,
-    pub tokens :          Vec<Token>,
+    pub stack :            Vec<i64>,
+    pub stack_limit :      Option<usize>,
+    pub tokens :           Vec<Token>,
+    /// Constants from the program's data sections, read by `dataload`
+    pub data :             Vec<i64>,
     /// Pointer to the position in execution
-    pub ptr :             i64,
-    pub token_limit :     Option<usize>,
-    pub tokens_consumed : usize,
+    pub ptr :              i64,
+    pub token_limit :      Option<usize>,
+    pub tokens_consumed :  usize,
+    /// How long the runtime may run for, measured from when it was created or
+    /// last reset
+    pub time_limit :       Option<Duration>,
+    /// How many values the stack and memory may hold between them
+    pub total_cell_limit : Option<usize>,
+    /// How far `goto`, `hop`, and `hopif` may move the pointer in one jump
+    pub max_jump :         Option<i64>,
+    /// The largest absolute value a literal in the program may have, checked
+    /// when it is pushed or loaded with `dataload`
+    pub max_literal :      Option<i64>,
+    /// The largest the stack has been during execution
+    pub peak_stack :       usize,
+    /// The largest value `runmax` has seen
+    pub running_max :      Option<i64>,
+    /// Variables for `set` and `get`, addressed `0` to `25`
+    pub registers :        [i64; 26],
+    /// Values kept apart from the stack, addressed from `0`
+    pub memory :           Vec<i64>,
     /// Whether or not there is more potential input to be considered
-    pub eof :             bool,
+    pub eof :              bool,
+    /// Whether the program has exited with a value, after which executing
+    /// errors with `RuntimeError::AlreadyHalted` until `Self::reset`
+    pub halted :           bool,
+    /// Where opperations like `dump` and `top` write their output
+    pub out :              Box<dyn Write>,
+    /// Where opperations like `readlinelen` read their input, stdin when
+    /// `None`
+    pub input :            Option<Box<dyn BufRead>>,
+    /// Flips the pop order of non-commutative opperations, see
+    /// `Self::pop_operands`
+    pub reverse_operands : bool,
+    /// How `add`, `sub`, and `mul` handle overflow
+    pub arith_mode :       ArithMode,
+    /// Rejects opperations whose results differ between runs, like `now`,
+    /// with `RuntimeError::NonDeterministic`
+    pub deterministic :    bool,
+    /// Debugging names for stack positions, shown by `dump`
+    pub labels :           HashMap<usize, String>,
+    /// When the runtime was created or last reset, the epoch for `now` and
+    /// `time_limit`
+    pub started :          Instant,
+    /// State from the start of the last `execute`, see `Self::rollback`
+    pub checkpoint :       Checkpoint,
+    /// Values popped by the current token, see `Self::pop`
+    pub operands :         Operands,
+    /// How many values at the bottom of the stack can be read but not popped
+    /// or changed, see `Self::protect`
+    pub readonly_below :   Option<usize>,
+    /// Opperations registered with `Self::register_op`
+    pub custom_ops :       HashMap<&'static str, CustomOp>,
+    /// Total time spent executing each opperation, only measured when this
+    /// is `Some` since timing every token slows execution down
+    pub op_times :         Option<HashMap<Opp, Duration>>,
+    /// Every token executed, in order, only recorded when this is `Some`
+    pub trace :            Option<Vec<TraceEvent>>,
 }
 
 impl Slug {
     #[must_use]
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
+        Self {
+            stack :            Vec::new(),
+            tokens :           Vec::new(),
+            data :             Vec::new(),
+            ptr :              0,
+            stack_limit :      None,
+            token_limit :      None,
+            tokens_consumed :  0,
+            time_limit :       None,
+            total_cell_limit : None,
+            max_jump :         None,
+            max_literal :      None,
+            peak_stack :       0,
+            running_max :      None,
+            registers :        [0; 26],
+            memory :           Vec::new(),
+            eof :              false,
+            halted :           false,
+            out :              Box::new(io::stdout()),
+            input :            None,
+            reverse_operands : false,
+            arith_mode :       ArithMode::default(),
+            deterministic :    false,
+            labels :           HashMap::new(),
+            started :          Instant::now(),
+            checkpoint :       Checkpoint::default(),
+            operands :         Operands::default(),
+            readonly_below :   None,
+            custom_ops :       HashMap::new(),
+            op_times :         None,
+            trace :            None,
+        }
+    }
+
+    /// Creates a runtime that reads its input from `input` rather than stdin,
+    /// so programs using opperations like `readlinelen` run the same every
+    /// time
+    #[must_use]
+    pub fn with_input_str(input : &str) -> Self {
+        Self {
+            input : Some(Box::new(Cursor::new(input.to_owned().into_bytes()))),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a runtime whose memory starts as `initial`, such as values
+    /// loaded from a data file, which the program can bring onto the stack
+    /// with `fillback`. The initial values count toward `total_cell_limit`,
+    /// so a limit below their number errors after the first token.
+    #[must_use]
+    pub fn with_memory(initial : Vec<i64>) -> Self {
         Self {
-            stack :           Vec::new(),
-            tokens :          Vec::new(),
-            ptr :             0,
-            stack_limit :     None,
-            token_limit :     None,
-            tokens_consumed : 0,
-            eof :             false,
+            memory : initial,
+            ..Self::new()
         }
     }
 
+    /// Registers a custom opperation which acts on the stack when a token
+    /// named `name` is executed. Use `Self::parse_program` to parse text that
+    /// uses it. Registering a name again replaces the previous opperation.
+    ///
+    /// The opperation changes the stack directly, so if it errors the stack is
+    /// left however it left it rather than being restored.
+    pub fn register_op(
+        &mut self,
+        name : &str,
+        op : impl Fn(&mut Vec<i64>) -> Result<(), RuntimeError> + 'static,
+    ) {
+        // Tokens refer to the name, so it's interned to live as long as the
+        // program
+        self.custom_ops.insert(intern(name), Box::new(op));
+    }
+
+    /// Parses a program, resolving words that aren't built in against the
+    /// opperations registered on this runtime
+    ///
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid, with an error for each
+    /// problem in order
+    pub fn parse_program(&self, text : &str) -> Result<Program, Vec<ParseTextError>> {
+        let custom : Vec<&'static str> = self.custom_ops.keys().copied().collect();
+        Tokenizer::parse_program_custom(text, &[], &custom)
+    }
+
+    /// Names a stack position (0 being the bottom) so that `dump` shows it as
+    /// `name = value`. This doesn't affect execution.
+    pub fn label_position(&mut self, index : usize, name : &str) {
+        self.labels.insert(index, name.to_owned());
+    }
+
+    /// Protects the bottom `k` values of the stack, such as arguments passed
+    /// in by an embedder, so the program can read them but popping or
+    /// rearranging them errors.
+    pub const fn protect(&mut self, k : usize) {
+        self.readonly_below = Some(k);
+    }
+
+    /// Describes the stack from bottom to top, one position per line, the
+    /// same way `dump` prints it.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        for (ptr, v) in self.stack.iter().enumerate() {
+            let _ = match self.labels.get(&ptr) {
+                Some(name) => writeln!(text, "{ptr} | {name} = {v}"),
+                None => writeln!(text, "{ptr} | {v}"),
+            };
+        }
+        text
+    }
+
+    /// Resets the execution state so the loaded tokens can be run again from
+    /// the start, keeping the tokens, limits, and output. The clock used by
+    /// `now` and `time_limit` restarts as well.
+    ///
+    /// Memory is cleared too, including any values seeded by
+    /// `Self::with_memory`, so set `memory` again before rerunning a program
+    /// that reads them.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.ptr = 0;
+        self.tokens_consumed = 0;
+        self.peak_stack = 0;
+        self.running_max = None;
+        self.registers = [0; 26];
+        self.memory.clear();
+        self.halted = false;
+        self.started = Instant::now();
+    }
+
     /// Execute a series of inputed tokens.
     ///
     /// # Errors
     ///
     /// See `Self::execute`
     pub fn execute_tokens(&mut self, toks : Vec<Token>) -> Result<Option<i64>, RuntimeError> {
+        self.checkpoint();
         self.tokens.extend(toks);
-        self.execute()
+        self.run()
     }
 
     /// Executes an inputted token.
@@ -45,8 +300,9 @@ impl Slug {
     ///
     /// See `Self::execute`
     pub fn execute_token(&mut self, token : Token) -> Result<Option<i64>, RuntimeError> {
+        self.checkpoint();
         self.tokens.push(token);
-        self.execute()
+        self.run()
     }
 
     /// Executes the current state of the runtime
@@ -55,12 +311,66 @@ impl Slug {
     ///
     /// This will error if the runtime enters and invalid state or attempts
     /// an invalid opperation.
-    #[expect(clippy::too_many_lines, reason = "Boo Hoo Clippy")]
     pub fn execute(&mut self) -> Result<Option<i64>, RuntimeError> {
+        self.checkpoint();
+        self.run()
+    }
+
+    /// Restores the state from before the last call to one of the `execute`
+    /// functions, dropping any tokens it added. This lets a Repl recover from
+    /// a bad line and carry on.
+    pub fn rollback(&mut self) {
+        let checkpoint = mem::take(&mut self.checkpoint);
+        self.stack = checkpoint.stack;
+        self.ptr = checkpoint.ptr;
+        self.tokens.truncate(checkpoint.tokens_len);
+        self.tokens_consumed = checkpoint.tokens_consumed;
+    }
+
+    fn checkpoint(&mut self) {
+        self.checkpoint = Checkpoint {
+            stack :           self.stack.clone(),
+            ptr :             self.ptr,
+            tokens_len :      self.tokens.len(),
+            tokens_consumed : self.tokens_consumed,
+        };
+    }
+
+    fn run(&mut self) -> Result<Option<i64>, RuntimeError> {
+        if self.halted {
+            return Err(RuntimeError::AlreadyHalted);
+        }
         if self.tokens.is_empty() && self.eof {
             return Err(RuntimeError::NoTokens);
         }
 
+        #[expect(
+            clippy::cast_possible_wrap,
+            reason = "The chances of someone writing a program with even over a trillon tokens is so insanely low that this would never happen in a real enviroment"
+        )]
+        let len = self.tokens.len() as i64;
+
+        // Nothing is left to run, either no tokens have been fed in yet or
+        // everything fed in so far has already been run
+        if self.ptr >= len {
+            return match (self.eof, self.ptr == len) {
+                (false, _) => Ok(None),
+                (true, true) => self.exit().map(Some),
+                (true, false) => Err(RuntimeError::AfterProgramRead),
+            };
+        }
+
+        if self.op_times.is_some() || self.trace.is_some() {
+            self.run_tokens::<true>()
+        } else {
+            self.run_tokens::<false>()
+        }
+    }
+
+    /// Runs tokens from the pointer onward. Timing every token slows
+    /// execution down, so it's only compiled into the `TIMED` version, which
+    /// records `op_times` and `trace`.
+    fn run_tokens<const TIMED: bool>(&mut self) -> Result<Option<i64>, RuntimeError> {
         loop {
             if self.ptr < 0 {
                 return Err(RuntimeError::BreforeProgramRead);
@@ -74,76 +384,41 @@ impl Slug {
                 clippy::cast_possible_truncation,
                 reason = "The chances of someone actually writing a program long enough and complex enough to cause a truncation error is so low that I doubt it would ever happen"
             )]
-            match self.tokens[self.ptr as usize] {
-                Token::Value(i) => self.stack.push(i),
-
-                Token::Opp(opp) => {
-                    match opp {
-                        Opp::Add => {
-                            let rhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let lhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.stack.push(lhs + rhs);
-                        },
-                        Opp::Sub => {
-                            let rhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let lhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.stack.push(lhs - rhs);
-                        },
-                        Opp::Mul => {
-                            let a1 = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let a2 = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.stack.push(a1 * a2);
-                        },
-                        Opp::Dump => {
-                            for (ptr, v) in self.stack.iter().enumerate() {
-                                println!("{ptr} | {v}");
-                            }
-                        },
-                        Opp::Top => {
-                            let a = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            println!("Top: {a}");
-                            self.stack.push(a);
-                        },
-                        Opp::Swap => {
-                            let a1 = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let a2 = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.stack.push(a1);
-                            self.stack.push(a2);
-                        },
-                        Opp::Drop => {
-                            self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                        },
-                        Opp::Hop => {
-                            let d = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.ptr += d;
-                        },
-                        Opp::Div => {
-                            let rhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let lhs = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.stack.push(lhs % rhs);
-                            self.stack.push(lhs / rhs);
-                        },
-                        Opp::Pos => {
-                            self.stack.push(self.ptr);
-                        },
-                        Opp::Exit => return self.exit().map(Some),
-                        Opp::Goto => {
-                            let v = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            self.ptr = v - 1; // Go to the token using 0 index rather than -1 index
-                        },
-                        Opp::Flip => {
-                            let t = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
-                            let b = self.stack[0];
-
-                            self.stack[0] = t;
-                            self.stack.push(b);
-                        },
-                    }
+            let token = self.tokens[self.ptr as usize];
+            let started = TIMED.then(Instant::now);
+            let index = self.ptr;
+            self.operands.popped.clear();
+            self.operands.depth = self.stack.len();
+            let exited = match self.step(token) {
+                Ok(exited) => exited,
+                Err(err) => {
+                    self.restore_operands();
+                    return Err(err);
                 },
+            };
+            if let Some(started) = started {
+                let duration = started.elapsed();
+                if let (Some(times), Token::Opp(op)) = (&mut self.op_times, token) {
+                    *times.entry(op).or_default() += duration;
+                }
+                if let Some(trace) = &mut self.trace {
+                    trace.push(TraceEvent {
+                        token,
+                        index,
+                        start : started.duration_since(self.started),
+                        duration,
+                    });
+                }
+            }
+            if exited.is_some() {
+                // The exiting token still ran
+                self.tokens_consumed += 1;
+                return Ok(exited);
             }
 
             self.ptr += 1;
             self.tokens_consumed += 1;
+            self.peak_stack = self.peak_stack.max(self.stack.len());
 
             // Only bother with token limit if it exists
             if let Some(limit) = self.token_limit
@@ -158,6 +433,18 @@ impl Slug {
                 return Err(RuntimeError::StackLimitHit(self.ptr));
             }
 
+            if let Some(limit) = self.total_cell_limit
+                && limit < self.cells()
+            {
+                return Err(RuntimeError::MemoryLimitHit(self.ptr));
+            }
+
+            if let Some(limit) = self.time_limit
+                && limit < self.started.elapsed()
+            {
+                return Err(RuntimeError::TimeLimitHit(self.ptr));
+            }
+
             #[expect(
                 clippy::cast_possible_wrap,
                 reason = "The chances of someone writing a program with even over a trillon tokens is so insanely low that this would never happen in a real enviroment"
@@ -178,13 +465,1009 @@ impl Slug {
         }
     }
 
+    /// Executes a single token, leaving the pointer on it. Returns the result
+    /// if the token exits the program.
+    ///
+    /// # Errors
+    /// See `Self::execute`
+    #[expect(clippy::too_many_lines, reason = "Boo Hoo Clippy")]
+    fn step(&mut self, token : Token) -> Result<Option<i64>, RuntimeError> {
+        match token {
+            Token::Value(i) => {
+                self.check_literal(i)?;
+                self.stack.push(i);
+            },
+
+            Token::Custom(name) => {
+                let op = self
+                    .custom_ops
+                    .get(name)
+                    .ok_or(RuntimeError::UnknownCustomOp(self.ptr))?;
+                // The opperation gets the whole stack so it can read protected
+                // values, so check afterwards that it left them alone
+                let protected = self
+                    .readonly_below
+                    .map(|k| self.stack[.. k.min(self.stack.len())].to_vec());
+                op(&mut self.stack)?;
+                if let Some(protected) = protected
+                    && !self.stack.starts_with(&protected)
+                {
+                    return Err(RuntimeError::ProtectedUnderflow(self.ptr));
+                }
+            },
+
+            Token::Opp(opp) => {
+                match opp {
+                    Opp::Add => {
+                        let rhs = self.pop()?;
+                        let lhs = self.pop()?;
+                        let v = self.arith(
+                            lhs,
+                            rhs,
+                            i64::wrapping_add,
+                            i64::checked_add,
+                            i64::saturating_add,
+                        )?;
+                        self.stack.push(v);
+                    },
+                    Opp::Sub => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        let v = self.arith(
+                            lhs,
+                            rhs,
+                            i64::wrapping_sub,
+                            i64::checked_sub,
+                            i64::saturating_sub,
+                        )?;
+                        self.stack.push(v);
+                    },
+                    Opp::Mul => {
+                        let a1 = self.pop()?;
+                        let a2 = self.pop()?;
+                        let v = self.arith(
+                            a1,
+                            a2,
+                            i64::wrapping_mul,
+                            i64::checked_mul,
+                            i64::saturating_mul,
+                        )?;
+                        self.stack.push(v);
+                    },
+                    Opp::Dump => {
+                        let text = self.describe();
+                        write!(self.out, "{text}")
+                            .map_err(|_| RuntimeError::WriteFailed(self.ptr))?;
+                    },
+                    Opp::Top => {
+                        let a = self.pop()?;
+                        writeln!(self.out, "Top: {a}")
+                            .map_err(|_| RuntimeError::WriteFailed(self.ptr))?;
+                        self.stack.push(a);
+                    },
+                    Opp::Swap => {
+                        let a1 = self.pop()?;
+                        let a2 = self.pop()?;
+                        self.stack.push(a1);
+                        self.stack.push(a2);
+                    },
+                    Opp::Drop => {
+                        self.pop()?;
+                    },
+                    Opp::Hop => {
+                        let d = self.pop()?;
+                        self.jump_by(d)?;
+                    },
+                    Opp::Div => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        if rhs == 0 {
+                            return Err(RuntimeError::DivByZero(self.ptr));
+                        }
+
+                        // `i64::MIN / -1` is the only other division that can fail
+                        let (Some(rem), Some(quot)) = (lhs.checked_rem(rhs), lhs.checked_div(rhs))
+                        else {
+                            return Err(RuntimeError::Overflow(self.ptr));
+                        };
+                        self.stack.push(rem);
+                        self.stack.push(quot);
+                    },
+                    Opp::Pos => {
+                        // The pointer only moves past this token after the match
+                        self.stack.push(self.ptr);
+                    },
+                    Opp::Exit => return self.exit().map(Some),
+                    Opp::Goto => {
+                        let v = self.pop()?;
+                        self.jump_to(v)?;
+                    },
+                    Opp::Flip => {
+                        self.check_unprotected(0)?;
+                        let t = self.pop()?;
+                        let b = self.stack[0];
+
+                        self.stack[0] = t;
+                        self.stack.push(b);
+                    },
+                    Opp::Sign => {
+                        let v = self.pop()?;
+                        self.stack.push(v.signum());
+                    },
+                    Opp::CountOf => {
+                        let target = self.pop()?;
+                        // A single pass over the stack, so the work is bounded by the
+                        // stack limit
+                        let count = self.stack.iter().filter(|v| **v == target).count();
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "The stack can't hold anywhere near i64::MAX values"
+                        )]
+                        self.stack.push(count as i64);
+                    },
+                    Opp::HopIf => {
+                        let cond = self.pop()?;
+                        let d = self.pop()?;
+                        if cond != 0 {
+                            self.jump_by(d)?;
+                        }
+                    },
+                    Opp::Now => {
+                        if self.deterministic {
+                            return Err(RuntimeError::NonDeterministic(self.ptr));
+                        }
+                        let ms = self.started.elapsed().as_millis();
+                        self.stack.push(i64::try_from(ms).unwrap_or(i64::MAX));
+                    },
+                    Opp::AssertEq => {
+                        let expected = self.pop()?;
+                        let actual = self.stack.last().ok_or(RuntimeError::UnderRead(self.ptr))?;
+                        if *actual != expected {
+                            return Err(RuntimeError::AssertionFailed(self.ptr));
+                        }
+                    },
+                    Opp::IndexOf => {
+                        let target = self.pop()?;
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "The stack can't hold anywhere near i64::MAX values"
+                        )]
+                        let idx = self
+                            .stack
+                            .iter()
+                            .position(|v| *v == target)
+                            .map_or(-1, |i| i as i64);
+                        self.stack.push(idx);
+                    },
+                    Opp::DataLoad => {
+                        let idx = self.pop()?;
+                        let v = usize::try_from(idx)
+                            .ok()
+                            .and_then(|i| self.data.get(i))
+                            .copied()
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        // Data is part of the program, so it's held to the same limit
+                        self.check_literal(v)?;
+                        self.stack.push(v);
+                    },
+                    Opp::SwapAt => {
+                        let j = self.pop()?;
+                        let i = self.pop()?;
+
+                        let len = self.stack.len();
+                        let (Ok(i), Ok(j)) = (usize::try_from(i), usize::try_from(j)) else {
+                            return Err(RuntimeError::IndexOutOfBounds(self.ptr));
+                        };
+                        if i >= len || j >= len {
+                            return Err(RuntimeError::IndexOutOfBounds(self.ptr));
+                        }
+                        self.check_unprotected(i.min(j))?;
+
+                        self.stack.swap(i, j);
+                    },
+                    Opp::AbsDiff => {
+                        let a1 = self.pop()?;
+                        let a2 = self.pop()?;
+                        let d = a1.abs_diff(a2);
+                        self.stack.push(i64::try_from(d).unwrap_or(i64::MAX));
+                    },
+                    Opp::MulPeek => {
+                        let [.., a2, a1] = self.stack[..] else {
+                            return Err(RuntimeError::UnderRead(self.ptr));
+                        };
+                        let product = a1.checked_mul(a2).ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(product);
+                    },
+                    Opp::Hash => self.stack.push(fnv1a(&self.stack).cast_signed()),
+                    Opp::OpCount => {
+                        let d = self.pop()?;
+                        let op = usize::try_from(d)
+                            .ok()
+                            .and_then(|d| Opp::all().get(d))
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        let count = self
+                            .tokens
+                            .iter()
+                            .filter(|tok| matches!(tok, Token::Opp(o) if o == op))
+                            .count();
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "No program has anywhere near i64::MAX tokens"
+                        )]
+                        self.stack.push(count as i64);
+                    },
+                    Opp::Digits => {
+                        let v = self.pop()?;
+                        if v < 0 {
+                            return Err(RuntimeError::NegativeOperand(self.ptr));
+                        }
+
+                        let digits : Vec<i64> =
+                            v.to_string().bytes().map(|b| i64::from(b - b'0')).collect();
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "An i64 has at most 19 digits"
+                        )]
+                        let count = digits.len() as i64;
+                        self.stack.extend(digits);
+                        self.stack.push(count);
+                    },
+                    Opp::Eval => {
+                        let n = self.pop()?;
+                        let result = self.eval(n)?;
+                        self.stack.push(result);
+                    },
+                    Opp::Clamp => {
+                        let max = self.pop()?;
+                        let min = self.pop()?;
+                        let v = self.pop()?;
+                        if min > max {
+                            return Err(RuntimeError::InvalidRange(self.ptr));
+                        }
+                        self.stack.push(v.clamp(min, max));
+                    },
+                    Opp::RunningMax => {
+                        let v = self.pop()?;
+                        let max = self.running_max.map_or(v, |max| max.max(v));
+                        self.running_max = Some(max);
+                        self.stack.push(max);
+                    },
+                    Opp::RotateN => {
+                        let n = self.pop()?;
+                        if !self.stack.is_empty() {
+                            self.check_unprotected(0)?;
+                            #[expect(
+                                clippy::cast_possible_wrap,
+                                clippy::cast_possible_truncation,
+                                reason = "The stack length fits in an i64 and `rem_euclid` \
+                                          gives a value in `0..len`"
+                            )]
+                            let k = n.rem_euclid(self.stack.len() as i64) as usize;
+                            self.stack.rotate_right(k);
+                        }
+                    },
+                    Opp::Recombine => {
+                        let divisor = self.pop()?;
+                        let quotient = self.pop()?;
+                        let remainder = self.pop()?;
+                        let dividend = quotient
+                            .checked_mul(divisor)
+                            .and_then(|v| v.checked_add(remainder))
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(dividend);
+                    },
+                    Opp::LogAnd => {
+                        let a1 = self.pop()?;
+                        let a2 = self.pop()?;
+                        self.stack.push(i64::from(a1 != 0 && a2 != 0));
+                    },
+                    Opp::LogOr => {
+                        let a1 = self.pop()?;
+                        let a2 = self.pop()?;
+                        self.stack.push(i64::from(a1 != 0 || a2 != 0));
+                    },
+                    Opp::Square => {
+                        let v = self.pop()?;
+                        let squared = v.checked_mul(v).ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(squared);
+                    },
+                    Opp::Cube => {
+                        let v = self.pop()?;
+                        let cubed = v
+                            .checked_mul(v)
+                            .and_then(|sq| sq.checked_mul(v))
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(cubed);
+                    },
+                    Opp::CountClear => {
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "The stack can't hold anywhere near i64::MAX values"
+                        )]
+                        let count = self.stack.len() as i64;
+                        self.check_unprotected(0)?;
+                        self.stack.clear();
+                        self.stack.push(count);
+                    },
+                    Opp::Set => {
+                        let idx = self.pop()?;
+                        let v = self.pop()?;
+                        let register = usize::try_from(idx)
+                            .ok()
+                            .and_then(|i| self.registers.get_mut(i))
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        *register = v;
+                    },
+                    Opp::Get => {
+                        let idx = self.pop()?;
+                        let v = usize::try_from(idx)
+                            .ok()
+                            .and_then(|i| self.registers.get(i))
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        self.stack.push(*v);
+                    },
+                    Opp::FloorDiv => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        if rhs == 0 {
+                            return Err(RuntimeError::DivByZero(self.ptr));
+                        }
+
+                        let mut quot = lhs
+                            .checked_div(rhs)
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        let mut rem = lhs % rhs;
+                        if rem != 0 && (rem < 0) != (rhs < 0) {
+                            quot -= 1;
+                            rem += rhs;
+                        }
+
+                        self.stack.push(rem);
+                        self.stack.push(quot);
+                    },
+                    Opp::Nop => {},
+                    Opp::InRange => {
+                        let max = self.pop()?;
+                        let min = self.pop()?;
+                        let v = self.pop()?;
+                        if min > max {
+                            return Err(RuntimeError::InvalidRange(self.ptr));
+                        }
+                        self.stack.push(i64::from((min ..= max).contains(&v)));
+                    },
+                    Opp::NegIf => {
+                        let cond = self.pop()?;
+                        let v = self.pop()?;
+                        if cond == 0 {
+                            self.stack.push(v);
+                        } else {
+                            self.stack
+                                .push(v.checked_neg().ok_or(RuntimeError::Overflow(self.ptr))?);
+                        }
+                    },
+                    Opp::Mean => {
+                        if self.stack.is_empty() {
+                            return Err(RuntimeError::UnderRead(self.ptr));
+                        }
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "The stack can't hold anywhere near i64::MAX values"
+                        )]
+                        let count = self.stack.len() as i64;
+                        self.check_unprotected(0)?;
+                        // Only clear the stack once the sum fits, so an
+                        // overflow leaves it alone
+                        let sum = self
+                            .stack
+                            .iter()
+                            .try_fold(0i64, |sum, v| sum.checked_add(*v))
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.clear();
+                        self.stack.push(sum / count);
+                    },
+                    Opp::ReadLineLen => {
+                        let mut line = Vec::new();
+                        let read = self
+                            .read_line(&mut line)
+                            .map_err(|_| RuntimeError::ReadFailed(self.ptr))?;
+
+                        if read == 0 {
+                            self.stack.push(-1);
+                        } else {
+                            let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                            let line = line.strip_suffix(b"\r").unwrap_or(line);
+                            #[expect(
+                                clippy::cast_possible_wrap,
+                                reason = "No line is anywhere near i64::MAX bytes"
+                            )]
+                            self.stack.push(line.len() as i64);
+                        }
+                    },
+                    Opp::PopCount => {
+                        let v = self.pop()?;
+                        self.stack.push(i64::from(v.count_ones()));
+                    },
+                    Opp::ReverseN => {
+                        let n = self.pop()?;
+                        let start = usize::try_from(n)
+                            .ok()
+                            .and_then(|n| self.stack.len().checked_sub(n))
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        self.check_unprotected(start)?;
+                        self.stack[start ..].reverse();
+                    },
+                    Opp::Fact => {
+                        let n = self.pop()?;
+                        if n < 0 {
+                            return Err(RuntimeError::NegativeOperand(self.ptr));
+                        }
+                        let fact = (2 ..= n)
+                            .try_fold(1i64, i64::checked_mul)
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(fact);
+                    },
+                    Opp::Fill => {
+                        let n = self.pop()?;
+                        let v = self.pop()?;
+                        let n = usize::try_from(n)
+                            .map_err(|_| RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        // Check before pushing so a huge count errors rather than allocating
+                        if let Some(limit) = self.stack_limit
+                            && limit < self.stack.len().saturating_add(n)
+                        {
+                            return Err(RuntimeError::StackLimitHit(self.ptr));
+                        }
+                        if let Some(limit) = self.total_cell_limit
+                            && limit < self.cells().saturating_add(n)
+                        {
+                            return Err(RuntimeError::MemoryLimitHit(self.ptr));
+                        }
+                        self.stack.extend(iter::repeat_n(v, n));
+                    },
+                    Opp::IsPrime => {
+                        let v = self.pop()?;
+                        let prime = u64::try_from(v).is_ok_and(is_prime);
+                        self.stack.push(i64::from(prime));
+                    },
+                    Opp::DigitSum => {
+                        let v = self.pop()?;
+                        if v < 0 {
+                            return Err(RuntimeError::NegativeOperand(self.ptr));
+                        }
+                        let sum = v.to_string().bytes().map(|b| i64::from(b - b'0')).sum();
+                        self.stack.push(sum);
+                    },
+                    Opp::DupIf => {
+                        let cond = self.pop()?;
+                        let top = *self.stack.last().ok_or(RuntimeError::UnderRead(self.ptr))?;
+                        if cond != 0 {
+                            self.stack.push(top);
+                        }
+                    },
+                    Opp::Spill => {
+                        self.check_unprotected(0)?;
+                        self.memory.append(&mut self.stack);
+                    },
+                    Opp::Fillback => {
+                        if let Some(limit) = self.stack_limit
+                            && limit < self.stack.len().saturating_add(self.memory.len())
+                        {
+                            return Err(RuntimeError::StackLimitHit(self.ptr));
+                        }
+                        self.stack.append(&mut self.memory);
+                    },
+                    Opp::Fib => {
+                        let n = self.pop()?;
+                        if n < 0 {
+                            return Err(RuntimeError::NegativeOperand(self.ptr));
+                        }
+                        // Stop one short of computing the number after the `n`th, which
+                        // would overflow first
+                        let (mut a, mut b) = (0i64, 1i64);
+                        for _ in 1 .. n {
+                            let next = a.checked_add(b).ok_or(RuntimeError::Overflow(self.ptr))?;
+                            a = b;
+                            b = next;
+                        }
+                        self.stack.push(if n == 0 { 0 } else { b });
+                    },
+                    Opp::AssertEmpty => {
+                        if !self.stack.is_empty() {
+                            return Err(RuntimeError::AssertionFailed(self.ptr));
+                        }
+                    },
+                    Opp::FloorPow2 => {
+                        let v = self.pop()?;
+                        if v <= 0 {
+                            return Err(RuntimeError::NonPositiveOperand(self.ptr));
+                        }
+                        self.stack.push(1 << (63 - v.leading_zeros()));
+                    },
+                    Opp::IsStreaming => self.stack.push(i64::from(!self.eof)),
+                    Opp::DistinctCount => {
+                        // A single pass over the stack, so the work is bounded by the
+                        // stack limit
+                        let distinct = self.stack.iter().collect::<HashSet<_>>().len();
+
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "The stack can't hold anywhere near i64::MAX values"
+                        )]
+                        self.stack.push(distinct as i64);
+                    },
+                    Opp::Len => {
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "No program has anywhere near i64::MAX tokens"
+                        )]
+                        self.stack.push(self.tokens.len() as i64);
+                    },
+                    Opp::ExitIf => {
+                        let cond = self.pop()?;
+                        if cond != 0 {
+                            return self.exit().map(Some);
+                        }
+                    },
+                    Opp::Byte => {
+                        let idx = self.pop()?;
+                        let v = self.pop()?;
+                        let byte = usize::try_from(idx)
+                            .ok()
+                            .and_then(|i| v.to_le_bytes().get(i).copied())
+                            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+                        self.stack.push(i64::from(byte));
+                    },
+                    Opp::SwapHalves => {
+                        let len = self.stack.len();
+                        let half = len / 2;
+                        if half > 0 {
+                            self.check_unprotected(0)?;
+                        }
+                        for i in 0 .. half {
+                            self.stack.swap(i, len - half + i);
+                        }
+                    },
+                    Opp::Dup => {
+                        let top = *self.stack.last().ok_or(RuntimeError::UnderRead(self.ptr))?;
+                        self.stack.push(top);
+                    },
+                    Opp::Log2 => {
+                        let v = self.pop()?;
+                        if v <= 0 {
+                            return Err(RuntimeError::NonPositiveOperand(self.ptr));
+                        }
+                        self.stack.push(i64::from(v.ilog2()));
+                    },
+                    Opp::Eq => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        self.stack.push(i64::from(lhs == rhs));
+                    },
+                    Opp::Lt => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        self.stack.push(i64::from(lhs < rhs));
+                    },
+                    Opp::Gt => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        self.stack.push(i64::from(lhs > rhs));
+                    },
+                    Opp::Jz => {
+                        let target = self.pop()?;
+                        let cond = self.pop()?;
+                        if cond == 0 {
+                            self.jump_to(target)?;
+                        }
+                    },
+                    Opp::Rol => {
+                        let amount = self.pop()?;
+                        let v = self.pop()?;
+                        #[expect(
+                            clippy::cast_possible_truncation,
+                            reason = "rem_euclid leaves a value from 0 to 63"
+                        )]
+                        let amount = amount.rem_euclid(64) as u32;
+                        self.stack.push(v.rotate_left(amount));
+                    },
+                    Opp::Ror => {
+                        let amount = self.pop()?;
+                        let v = self.pop()?;
+                        #[expect(
+                            clippy::cast_possible_truncation,
+                            reason = "rem_euclid leaves a value from 0 to 63"
+                        )]
+                        let amount = amount.rem_euclid(64) as u32;
+                        self.stack.push(v.rotate_right(amount));
+                    },
+                    Opp::Neg => {
+                        let v = self.pop()?;
+                        let neg = v.checked_neg().ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(neg);
+                    },
+                    Opp::Median => {
+                        if self.stack.is_empty() {
+                            return Err(RuntimeError::UnderRead(self.ptr));
+                        }
+
+                        self.check_unprotected(0)?;
+                        let mut values = mem::take(&mut self.stack);
+                        values.sort_unstable();
+                        self.stack.push(values[(values.len() - 1) / 2]);
+                    },
+                    Opp::Mod => {
+                        let (lhs, rhs) = self.pop_operands()?;
+                        if rhs == 0 {
+                            return Err(RuntimeError::DivByZero(self.ptr));
+                        }
+                        let rem = lhs
+                            .checked_rem(rhs)
+                            .ok_or(RuntimeError::Overflow(self.ptr))?;
+                        self.stack.push(rem);
+                    },
+                    Opp::In => {
+                        let word = self
+                            .read_word()
+                            .map_err(|_| RuntimeError::ReadFailed(self.ptr))?;
+                        let v = str::from_utf8(&word)
+                            .ok()
+                            .and_then(|word| word.parse().ok())
+                            .ok_or(RuntimeError::InputError(self.ptr))?;
+                        self.stack.push(v);
+                    },
+                    Opp::Emit => {
+                        let v = self.pop()?;
+                        let c = u32::try_from(v)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or(RuntimeError::BadChar(self.ptr))?;
+                        write!(self.out, "{c}").map_err(|_| RuntimeError::WriteFailed(self.ptr))?;
+                    },
+                }
+            },
+        }
+
+        Ok(None)
+    }
+
+    /// Pops the top of the stack, remembering it in `Self::operands` so it
+    /// can be put back if the current token errors
+    ///
+    /// # Errors
+    /// Errors if the stack is empty or the top is protected
+    fn pop(&mut self) -> Result<i64, RuntimeError> {
+        if self.stack.is_empty() {
+            return Err(RuntimeError::UnderRead(self.ptr));
+        }
+        self.check_unprotected(self.stack.len() - 1)?;
+        let v = self.stack.pop().ok_or(RuntimeError::UnderRead(self.ptr))?;
+        if self.stack.len() < self.operands.depth {
+            self.operands.depth = self.stack.len();
+            self.operands.popped.push(v);
+        }
+        Ok(v)
+    }
+
+    /// Puts back the values the current token popped and drops anything it
+    /// pushed, so an erroring token leaves the stack as it found it. Custom
+    /// opperations change the stack without `Self::pop`, so aren't undone.
+    fn restore_operands(&mut self) {
+        self.stack.truncate(self.operands.depth);
+        self.stack.extend(self.operands.popped.drain(..).rev());
+    }
+
+    /// Checks that the values from `start` to the top of the stack aren't
+    /// protected, before an opperation removes or rearranges them
+    ///
+    /// # Errors
+    /// Errors if any of the values are protected
+    const fn check_unprotected(&self, start : usize) -> Result<(), RuntimeError> {
+        match self.readonly_below {
+            Some(k) if start < k => Err(RuntimeError::ProtectedUnderflow(self.ptr)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies whichever of the three versions of an arithmetic opperation
+    /// matches `arith_mode`
+    ///
+    /// # Errors
+    /// Errors if the result overflows in `ArithMode::Checked`
+    fn arith(
+        &self,
+        lhs : i64,
+        rhs : i64,
+        wrapping : fn(i64, i64) -> i64,
+        checked : fn(i64, i64) -> Option<i64>,
+        saturating : fn(i64, i64) -> i64,
+    ) -> Result<i64, RuntimeError> {
+        match self.arith_mode {
+            ArithMode::Wrapping => Ok(wrapping(lhs, rhs)),
+            ArithMode::Checked => checked(lhs, rhs).ok_or(RuntimeError::Overflow(self.ptr)),
+            ArithMode::Saturating => Ok(saturating(lhs, rhs)),
+        }
+    }
+
+    /// Pops the two operands of a binary opperation, returning `(lhs, rhs)`.
+    ///
+    /// By default the top of the stack is the right hand side, so `5 3 sub`
+    /// computes `5 - 3`. When `reverse_operands` is set the top of the stack
+    /// is the left hand side instead, so `5 3 sub` computes `3 - 5`.
+    ///
+    /// # Errors
+    /// Errors if there are fewer than two values on the stack
+    fn pop_operands(&mut self) -> Result<(i64, i64), RuntimeError> {
+        let top = self.pop()?;
+        let below = self.pop()?;
+
+        if self.reverse_operands {
+            Ok((top, below))
+        } else {
+            Ok((below, top))
+        }
+    }
+
+    /// Reads up to and including the next newline from the input, returning
+    /// the number of bytes read
+    fn read_line(&mut self, buf : &mut Vec<u8>) -> io::Result<usize> {
+        match &mut self.input {
+            Some(input) => input.read_until(b'\n', buf),
+            None => io::stdin().lock().read_until(b'\n', buf),
+        }
+    }
+
+    /// Reads the next word from the input, skipping any whitespace before it.
+    /// The word is empty if the input ends first.
+    #[expect(
+        clippy::option_if_let_else,
+        reason = "Clippy's 'solution' is much less readable"
+    )]
+    fn read_word(&mut self) -> io::Result<Vec<u8>> {
+        match &mut self.input {
+            Some(input) => read_word(input),
+            None => read_word(&mut io::stdin().lock()),
+        }
+    }
+
+    /// Moves the pointer so that the token at index `target` is the next to
+    /// execute, so `0 goto` runs the first token next.
+    ///
+    /// # Errors
+    /// Errors if the jump is further than `max_jump` allows
+    fn jump_to(&mut self, target : i64) -> Result<(), RuntimeError> {
+        // The pointer steps forward after every token, so it's left one
+        // before the target. This is the only place that adjustment is made.
+        // Saturate so a target of `i64::MIN` errors rather than overflowing.
+        self.jump_by(target.saturating_sub(self.ptr).saturating_sub(1))
+    }
+
+    /// Moves the pointer by `d`, before it steps past the current token.
+    ///
+    /// # Errors
+    /// Errors if `d` is further than `max_jump` allows
+    fn jump_by(&mut self, d : i64) -> Result<(), RuntimeError> {
+        self.check_jump(d)?;
+        // The pointer steps past the current token afterwards, so check that
+        // step can't overflow either
+        match self.ptr.checked_add(d).and_then(|ptr| ptr.checked_add(1)) {
+            Some(_) => {
+                self.ptr += d;
+                Ok(())
+            },
+            None if d > 0 => Err(RuntimeError::AfterProgramRead),
+            None => Err(RuntimeError::BreforeProgramRead),
+        }
+    }
+
+    /// How many values the stack and memory hold between them, as limited by
+    /// `total_cell_limit`
+    const fn cells(&self) -> usize {
+        self.stack.len().saturating_add(self.memory.len())
+    }
+
+    /// Checks that a value written in the program is allowed by `max_literal`
+    ///
+    /// # Errors
+    /// Errors if `v` is larger than `max_literal` allows
+    const fn check_literal(&self, v : i64) -> Result<(), RuntimeError> {
+        match self.max_literal {
+            Some(max) if v.unsigned_abs() > max.unsigned_abs() => {
+                Err(RuntimeError::LiteralTooLarge(self.ptr))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that moving the pointer by `d` is allowed by `max_jump`
+    ///
+    /// # Errors
+    /// Errors if `d` is further than `max_jump` allows
+    const fn check_jump(&self, d : i64) -> Result<(), RuntimeError> {
+        match self.max_jump {
+            Some(max) if d.unsigned_abs() > max.unsigned_abs() => {
+                Err(RuntimeError::JumpTooFar(self.ptr))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs the top `n` values of the stack as a program in a child runtime,
+    /// returning its result. The child shares this runtime's limits, counting
+    /// against the remaining token limit, so nested `eval`s stay bounded.
+    ///
+    /// # Errors
+    /// Errors if the codes are invalid or the child program errors
+    fn eval(&mut self, n : i64) -> Result<i64, RuntimeError> {
+        let n = usize::try_from(n).map_err(|_| RuntimeError::IndexOutOfBounds(self.ptr))?;
+        let start = self
+            .stack
+            .len()
+            .checked_sub(n)
+            .ok_or(RuntimeError::UnderRead(self.ptr))?;
+        self.check_unprotected(start)?;
+        let codes = self.stack.split_off(start);
+        let result = self.eval_codes(&codes);
+        if result.is_err() {
+            // Put the codes back so a failed `eval` leaves the stack alone
+            self.stack.extend(codes);
+        }
+        result
+    }
+
+    /// Runs `codes` as a program in a child runtime, see `Self::eval`
+    ///
+    /// # Errors
+    /// Errors if the codes are invalid or the child program errors
+    fn eval_codes(&mut self, codes : &[i64]) -> Result<i64, RuntimeError> {
+        let tokens = codes
+            .iter()
+            .map(|&code| Token::from_code(code))
+            .collect::<Option<Vec<Token>>>()
+            .ok_or(RuntimeError::IndexOutOfBounds(self.ptr))?;
+
+        let mut child = Self {
+            stack_limit : self.stack_limit,
+            tokens,
+            data : self.data.clone(),
+            token_limit : self
+                .token_limit
+                .map(|limit| limit.saturating_sub(self.tokens_consumed)),
+            time_limit : self.time_limit,
+            // The child's values count toward the limit along with the values
+            // still held here
+            total_cell_limit : self
+                .total_cell_limit
+                .map(|limit| limit.saturating_sub(self.cells())),
+            max_jump : self.max_jump,
+            max_literal : self.max_literal,
+            eof : true,
+            out : mem::replace(&mut self.out, Box::new(io::sink())),
+            input : self.input.take(),
+            reverse_operands : self.reverse_operands,
+            arith_mode : self.arith_mode,
+            started : self.started,
+            ..Self::new()
+        };
+
+        let result = child.execute();
+        self.out = child.out;
+        self.input = child.input;
+        self.tokens_consumed += child.tokens_consumed;
+
+        // The child's token indices mean nothing in this program, so report
+        // its errors at the `eval`
+        result
+            .map_err(|err| err.at(self.ptr))?
+            .ok_or(RuntimeError::NoOut)
+    }
+
     /// Exits the program
     ///
     /// # Errors
     /// This will return an error if the stack is empty, otherwise it will
     /// return the topmost value
     pub fn exit(&mut self) -> Result<i64, RuntimeError> {
-        self.stack.pop().ok_or(RuntimeError::NoOut)
+        let out = self.stack.pop().ok_or(RuntimeError::NoOut)?;
+        self.halted = true;
+        Ok(out)
+    }
+
+    /// Exits the program returning every value on the stack, bottom to top
+    ///
+    /// # Errors
+    /// This will return an error if the stack is empty
+    pub fn exit_all(&mut self) -> Result<Vec<i64>, RuntimeError> {
+        if self.stack.is_empty() {
+            Err(RuntimeError::NoOut)
+        } else {
+            Ok(mem::take(&mut self.stack))
+        }
+    }
+}
+
+/// 64 bit FNV-1a over the little endian bytes of each value in order
+fn fnv1a(values : &[i64]) -> u64 {
+    const OFFSET_BASIS : u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME : u64 = 0x0100_0000_01b3;
+
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
+
+/// Deterministic Miller-Rabin, which is exact for every 64 bit value with
+/// the first twelve primes as bases
+fn is_prime(n : u64) -> bool {
+    const BASES : [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "The remainder is less than `n`"
+    )]
+    let mul_mod = |a : u64, b : u64| (u128::from(a) * u128::from(b) % u128::from(n)) as u64;
+    let pow_mod = |mut base : u64, mut exp : u64| {
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = mul_mod(result, base);
+            }
+            base = mul_mod(base, base);
+            exp >>= 1;
+        }
+        result
+    };
+
+    // n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    BASES.iter().all(|&a| {
+        let mut x = pow_mod(a, d);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1 .. s).any(|_| {
+            x = mul_mod(x, x);
+            x == n - 1
+        })
+    })
+}
+
+/// Reads bytes up to the next whitespace, skipping any whitespace before them
+fn read_word(input : &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut word = Vec::new();
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(word);
+        }
+
+        let mut used = 0;
+        let mut done = false;
+        for byte in buf {
+            used += 1;
+            if !byte.is_ascii_whitespace() {
+                word.push(*byte);
+            } else if !word.is_empty() {
+                done = true;
+                break;
+            }
+        }
+        input.consume(used);
+
+        if done {
+            return Ok(word);
+        }
     }
 }
 
@@ -193,3 +1476,1018 @@ impl Default for Slug {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// Runs a program to completion with its output discarded
+    fn run(text : &str) -> Result<Option<i64>, RuntimeError> {
+        run_with(Slug::new(), text)
+    }
+
+    /// Runs a program to completion on `runtime` with its output discarded
+    fn run_with(mut runtime : Slug, text : &str) -> Result<Option<i64>, RuntimeError> {
+        runtime.tokens = Tokenizer::parse_text(text).expect("Test programs should parse");
+        runtime.eof = true;
+        runtime.out = Box::new(io::sink());
+        runtime.execute()
+    }
+
+    /// Runs a program without ending it, returning the stack it leaves
+    fn stack(text : &str) -> Vec<i64> {
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text(text).expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        runtime.stack
+    }
+
+    /// A writer whose output can still be read after the runtime takes it
+    #[derive(Clone, Default)]
+    struct Captured(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sign() {
+        assert_eq!(run("-7 sign"), Ok(Some(-1)));
+        assert_eq!(run("0 sign"), Ok(Some(0)));
+        assert_eq!(run("42 sign"), Ok(Some(1)));
+        assert_eq!(run("sign"), Err(RuntimeError::UnderRead(0)));
+    }
+
+    #[test]
+    fn count_of() {
+        assert_eq!(stack("3 1 3 3 3 countof"), vec![3, 1, 3, 3, 3]);
+        assert_eq!(run("3 1 3 3 3 countof"), Ok(Some(3)));
+        assert_eq!(run("3 1 3 7 countof"), Ok(Some(0)));
+    }
+
+    #[test]
+    fn reverse_operands() {
+        assert_eq!(run("5 3 sub"), Ok(Some(2)));
+        let reversed = Slug {
+            reverse_operands : true,
+            ..Slug::new()
+        };
+        assert_eq!(run_with(reversed, "5 3 sub"), Ok(Some(-2)));
+    }
+
+    #[test]
+    fn labeled_positions() {
+        let mut runtime = Slug::new();
+        runtime.stack = vec![4, 9];
+        runtime.label_position(1, "count");
+        assert_eq!(runtime.describe(), "0 | 4\n1 | count = 9\n");
+    }
+
+    #[test]
+    fn hop_if() {
+        assert_eq!(run("2 1 hopif 99 98 7"), Ok(Some(7)));
+        assert_eq!(run("2 0 hopif 99"), Ok(Some(99)));
+        assert_eq!(run("-10 1 hopif"), Err(RuntimeError::BreforeProgramRead));
+        assert_eq!(run("10 1 hopif"), Err(RuntimeError::AfterProgramRead));
+    }
+
+    #[test]
+    fn now_never_goes_backwards() {
+        let times = stack("now now");
+        assert!(times[0] <= times[1]);
+    }
+
+    #[test]
+    fn asserteq() {
+        assert_eq!(run("4 4 asserteq"), Ok(Some(4)));
+        assert_eq!(run("4 5 asserteq"), Err(RuntimeError::AssertionFailed(2)));
+    }
+
+    #[test]
+    fn rollback_after_error() {
+        let mut runtime = Slug::new();
+        let feed = |runtime : &mut Slug, text : &str| {
+            runtime.execute_tokens(Tokenizer::parse_text(text).expect("Test programs should parse"))
+        };
+
+        assert_eq!(feed(&mut runtime, "6"), Ok(None));
+        assert_eq!(feed(&mut runtime, "0 div"), Err(RuntimeError::DivByZero(2)));
+        runtime.rollback();
+        assert_eq!(runtime.stack, vec![6]);
+
+        assert_eq!(feed(&mut runtime, "3 div"), Ok(None));
+        assert_eq!(runtime.stack, vec![0, 2]);
+    }
+
+    #[test]
+    fn index_of() {
+        assert_eq!(run("5 6 7 5 indexof"), Ok(Some(0)));
+        assert_eq!(run("5 6 7 6 indexof"), Ok(Some(1)));
+        assert_eq!(run("5 6 7 7 indexof"), Ok(Some(2)));
+        assert_eq!(run("5 7 6 7 7 indexof"), Ok(Some(1)));
+        assert_eq!(run("5 6 7 9 indexof"), Ok(Some(-1)));
+    }
+
+    /// Parses a program with data sections and runs it to completion
+    fn run_program(text : &str) -> Result<Option<i64>, RuntimeError> {
+        let program = Tokenizer::parse_program(text, &[]).expect("Test programs should parse");
+        let mut runtime = Slug {
+            tokens : program.tokens,
+            data : program.data,
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.execute()
+    }
+
+    #[test]
+    fn data_sections() {
+        assert_eq!(run_program("data 10 20 30 end 0 dataload"), Ok(Some(10)));
+        assert_eq!(
+            run_program("data 10 20 end 1 dataload data 30 end 2 dataload add"),
+            Ok(Some(50))
+        );
+        assert_eq!(
+            run_program("data 10 end 1 dataload"),
+            Err(RuntimeError::IndexOutOfBounds(1))
+        );
+        // Data values aren't tokens, so `pos` is still at index 1
+        assert_eq!(run_program("data 10 20 30 end 7 pos"), Ok(Some(1)));
+    }
+
+    #[test]
+    fn swap_at() {
+        assert_eq!(stack("1 2 3 0 2 swapat"), vec![3, 2, 1]);
+        assert_eq!(stack("1 2 3 1 1 swapat"), vec![1, 2, 3]);
+        assert_eq!(
+            run("1 2 3 0 3 swapat"),
+            Err(RuntimeError::IndexOutOfBounds(5))
+        );
+        assert_eq!(
+            run("1 2 3 -1 0 swapat"),
+            Err(RuntimeError::IndexOutOfBounds(5))
+        );
+    }
+
+    #[test]
+    fn abs_diff() {
+        assert_eq!(run("3 10 absdiff"), Ok(Some(7)));
+        assert_eq!(run("10 3 absdiff"), Ok(Some(7)));
+        assert_eq!(run("-5 5 absdiff"), Ok(Some(10)));
+        assert_eq!(run("5 -5 absdiff"), Ok(Some(10)));
+        assert_eq!(
+            run("-9223372036854775808 9223372036854775807 absdiff"),
+            Ok(Some(i64::MAX))
+        );
+    }
+
+    #[test]
+    fn mul_peek() {
+        assert_eq!(stack("3 4 mulpeek"), vec![3, 4, 12]);
+        assert_eq!(run("3 mulpeek"), Err(RuntimeError::UnderRead(1)));
+    }
+
+    #[test]
+    fn hash() {
+        assert_eq!(fnv1a(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(&[1, 2, 3]), 0xda2b_fb22_5e0d_1f05);
+        assert_eq!(
+            stack("1 2 3 hash"),
+            vec![1, 2, 3, 0xda2b_fb22_5e0d_1f05_u64.cast_signed()]
+        );
+    }
+
+    #[test]
+    fn op_count() {
+        // `add` is discriminant 0
+        assert_eq!(run("1 2 add 3 add 0 opcount"), Ok(Some(2)));
+        assert_eq!(run("1 2 sub 0 opcount"), Ok(Some(0)));
+        assert_eq!(run("-1 opcount"), Err(RuntimeError::IndexOutOfBounds(1)));
+    }
+
+    #[test]
+    fn empty_stream_waits_for_tokens() {
+        let mut runtime = Slug::new();
+        assert_eq!(runtime.execute(), Ok(None));
+
+        runtime.eof = true;
+        assert_eq!(runtime.execute(), Err(RuntimeError::NoTokens));
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!(stack("907 digits"), vec![9, 0, 7, 3]);
+        assert_eq!(stack("4 digits"), vec![4, 1]);
+        assert_eq!(stack("0 digits"), vec![0, 1]);
+        assert_eq!(run("-1 digits"), Err(RuntimeError::NegativeOperand(1)));
+    }
+
+    #[test]
+    fn eval() {
+        // `-1` is the code for `add`
+        assert_eq!(run("2 3 -1 3 eval"), Ok(Some(5)));
+
+        // The child runs three tokens, but only one is left of the limit
+        let limited = Slug {
+            token_limit : Some(5),
+            ..Slug::new()
+        };
+        assert!(matches!(
+            run_with(limited, "2 3 -1 3 eval"),
+            Err(RuntimeError::TokenLimitHit(_))
+        ));
+
+        // The child's `add` underflows, which is reported at the `eval` with
+        // the code put back
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens =
+            Tokenizer::parse_text("7 8 -1 1 eval").expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Err(RuntimeError::UnderRead(4)));
+        assert_eq!(runtime.stack, vec![7, 8, -1, 1]);
+    }
+
+    #[test]
+    fn clamp() {
+        assert_eq!(run("-3 0 10 clamp"), Ok(Some(0)));
+        assert_eq!(run("4 0 10 clamp"), Ok(Some(4)));
+        assert_eq!(run("12 0 10 clamp"), Ok(Some(10)));
+        assert_eq!(run("4 10 0 clamp"), Err(RuntimeError::InvalidRange(3)));
+    }
+
+    #[test]
+    fn running_max() {
+        assert_eq!(
+            stack("3 runmax 1 runmax 5 runmax 2 runmax"),
+            vec![3, 3, 5, 5]
+        );
+        assert_eq!(stack("-4 runmax -9 runmax"), vec![-4, -4]);
+
+        let mut runtime = Slug::new();
+        runtime.tokens = Tokenizer::parse_text("8 runmax").expect("Test programs should parse");
+        runtime.eof = true;
+        runtime.out = Box::new(io::sink());
+        assert_eq!(runtime.execute(), Ok(Some(8)));
+        assert_eq!(runtime.running_max, Some(8));
+        runtime.reset();
+        assert_eq!(runtime.running_max, None);
+    }
+
+    #[test]
+    fn rotate_n() {
+        assert_eq!(stack("1 2 3 4 1 rotaten"), vec![4, 1, 2, 3]);
+        assert_eq!(stack("1 2 3 4 -1 rotaten"), vec![2, 3, 4, 1]);
+        assert_eq!(stack("1 2 3 4 6 rotaten"), vec![3, 4, 1, 2]);
+        assert_eq!(stack("3 rotaten"), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn pos() {
+        assert_eq!(run("pos top"), Ok(Some(0)));
+        assert_eq!(stack("5 6 pos"), vec![5, 6, 2]);
+        // Jumping relative to `pos` skips the `100`
+        assert_eq!(stack("pos 5 add goto 100 7"), vec![7]);
+    }
+
+    #[test]
+    fn recombine() {
+        assert_eq!(run("17 5 div 5 recombine"), Ok(Some(17)));
+        assert_eq!(run("-17 5 div 5 recombine"), Ok(Some(-17)));
+        assert_eq!(
+            run("1 9223372036854775807 2 recombine"),
+            Err(RuntimeError::Overflow(3))
+        );
+    }
+
+    #[test]
+    fn logical_and_or() {
+        // A bitwise `and` of 1 and 2 would be 0, and `or` of them would be 3
+        assert_eq!(run("1 2 land"), Ok(Some(1)));
+        assert_eq!(run("1 2 lor"), Ok(Some(1)));
+        assert_eq!(run("-5 0 land"), Ok(Some(0)));
+        assert_eq!(run("-5 0 lor"), Ok(Some(1)));
+        assert_eq!(run("0 0 lor"), Ok(Some(0)));
+    }
+
+    #[test]
+    fn square_and_cube() {
+        assert_eq!(run("-7 sq"), Ok(Some(49)));
+        assert_eq!(run("-3 cube"), Ok(Some(-27)));
+        assert_eq!(run("4294967296 sq"), Err(RuntimeError::Overflow(1)));
+        assert_eq!(run("2097152 cube"), Err(RuntimeError::Overflow(1)));
+    }
+
+    #[test]
+    fn custom_op() {
+        let mut runtime = Slug::new();
+        runtime.register_op("double", |stack| {
+            let v = stack.pop().ok_or(RuntimeError::UnderRead(0))?;
+            stack.push(v * 2);
+            Ok(())
+        });
+        let program = runtime
+            .parse_program("21 double")
+            .expect("Test programs should parse");
+        runtime.tokens = program.tokens;
+        runtime.eof = true;
+        runtime.out = Box::new(io::sink());
+        assert_eq!(runtime.execute(), Ok(Some(42)));
+
+        let errs = Slug::new()
+            .parse_program("21 double")
+            .expect_err("`double` isn't registered");
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].idx, 1);
+    }
+
+    #[test]
+    fn count_clear() {
+        assert_eq!(stack("4 5 6 countclear"), vec![3]);
+        assert_eq!(stack("countclear"), vec![0]);
+    }
+
+    #[test]
+    fn set_get() {
+        assert_eq!(run("42 3 set 7 25 set 3 get"), Ok(Some(42)));
+        assert_eq!(run("5 get"), Ok(Some(0)));
+        assert_eq!(run("1 26 set"), Err(RuntimeError::IndexOutOfBounds(2)));
+        assert_eq!(run("-1 get"), Err(RuntimeError::IndexOutOfBounds(1)));
+    }
+
+    #[test]
+    fn floor_div() {
+        assert_eq!(run("7 2 fdiv"), Ok(Some(3)));
+        assert_eq!(run("-7 2 fdiv"), Ok(Some(-4)));
+        assert_eq!(run("7 -2 fdiv"), Ok(Some(-4)));
+        assert_eq!(run("-7 -2 fdiv"), Ok(Some(3)));
+        assert_eq!(run("-6 2 fdiv"), Ok(Some(-3)));
+        assert_eq!(run("7 0 fdiv"), Err(RuntimeError::DivByZero(2)));
+    }
+
+    #[test]
+    fn nop() {
+        assert_eq!(run("1 2 nop add nop"), Ok(Some(3)));
+        assert_eq!(stack("3 goto 99 nop 5"), vec![5]);
+    }
+
+    #[test]
+    fn errors_keep_operands() {
+        let failed = |text : &str| {
+            let mut runtime = Slug {
+                out : Box::new(io::sink()),
+                ..Slug::new()
+            };
+            runtime.tokens = Tokenizer::parse_text(text).expect("Test programs should parse");
+            let err = runtime.execute().expect_err("Test programs should error");
+            (err, runtime.stack)
+        };
+        assert_eq!(failed("7 add"), (RuntimeError::UnderRead(1), vec![7]));
+        assert_eq!(failed("1 7 add add"), (RuntimeError::UnderRead(3), vec![8]));
+        assert_eq!(
+            failed("2 3 add 0 div"),
+            (RuntimeError::DivByZero(4), vec![5, 0])
+        );
+    }
+
+    #[test]
+    fn in_range() {
+        assert_eq!(run("5 1 10 inrange"), Ok(Some(1)));
+        assert_eq!(run("1 1 10 inrange"), Ok(Some(1)));
+        assert_eq!(run("10 1 10 inrange"), Ok(Some(1)));
+        assert_eq!(run("0 1 10 inrange"), Ok(Some(0)));
+        assert_eq!(run("11 1 10 inrange"), Ok(Some(0)));
+        assert_eq!(run("5 10 1 inrange"), Err(RuntimeError::InvalidRange(3)));
+    }
+
+    #[test]
+    fn neg_if() {
+        assert_eq!(run("5 1 negif"), Ok(Some(-5)));
+        assert_eq!(run("-5 -3 negif"), Ok(Some(5)));
+        assert_eq!(run("5 0 negif"), Ok(Some(5)));
+        assert_eq!(run("-9223372036854775808 0 negif"), Ok(Some(i64::MIN)));
+        assert_eq!(
+            run("-9223372036854775808 1 negif"),
+            Err(RuntimeError::Overflow(2))
+        );
+    }
+
+    #[test]
+    fn max_jump() {
+        let limited = || {
+            Slug {
+                max_jump : Some(2),
+                ..Slug::new()
+            }
+        };
+        assert_eq!(run_with(limited(), "4 goto 1 2 3"), Ok(Some(3)));
+        assert_eq!(
+            run_with(limited(), "5 goto 1 2 3 4"),
+            Err(RuntimeError::JumpTooFar(1))
+        );
+        assert_eq!(
+            run_with(limited(), "-3 hop"),
+            Err(RuntimeError::JumpTooFar(1))
+        );
+    }
+
+    #[test]
+    fn mean() {
+        assert_eq!(stack("2 4 9 mean"), vec![5]);
+        assert_eq!(run("-3 -4 mean"), Ok(Some(-3)));
+        assert_eq!(run("mean"), Err(RuntimeError::UnderRead(0)));
+
+        // An overflowing sum leaves the stack as it was
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("5 9223372036854775807 1 mean")
+            .expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Err(RuntimeError::Overflow(3)));
+        assert_eq!(runtime.stack, vec![5, i64::MAX, 1]);
+    }
+
+    #[test]
+    fn read_line_len() {
+        let mut runtime = Slug {
+            input : Some(Box::new(Cursor::new(b"hello\r\nhi\n".to_vec()))),
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("readlinelen readlinelen readlinelen")
+            .expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        assert_eq!(runtime.stack, vec![5, 2, -1]);
+    }
+
+    #[test]
+    fn pop_count() {
+        assert_eq!(run("0 popcount"), Ok(Some(0)));
+        assert_eq!(run("64 popcount"), Ok(Some(1)));
+        assert_eq!(run("11 popcount"), Ok(Some(3)));
+        assert_eq!(run("-1 popcount"), Ok(Some(64)));
+        assert_eq!(run("-9223372036854775808 popcount"), Ok(Some(1)));
+    }
+
+    #[test]
+    fn reverse_n() {
+        assert_eq!(stack("1 2 3 3 reversen"), vec![3, 2, 1]);
+        assert_eq!(stack("1 2 3 4 2 reversen"), vec![1, 2, 4, 3]);
+        assert_eq!(stack("1 2 0 reversen"), vec![1, 2]);
+        assert_eq!(
+            run("1 2 3 reversen"),
+            Err(RuntimeError::IndexOutOfBounds(3))
+        );
+        assert_eq!(
+            run("1 2 -1 reversen"),
+            Err(RuntimeError::IndexOutOfBounds(3))
+        );
+    }
+
+    #[test]
+    fn protected_values() {
+        let run_protected = |text : &str| {
+            let mut runtime = Slug::new();
+            runtime.stack = vec![1, 2];
+            runtime.protect(2);
+            runtime.register_op("discard", |stack| {
+                stack.pop().ok_or(RuntimeError::UnderRead(0))?;
+                Ok(())
+            });
+            let program = runtime
+                .parse_program(text)
+                .expect("Test programs should parse");
+            runtime.tokens = program.tokens;
+            runtime.out = Box::new(io::sink());
+            runtime.execute()
+        };
+        assert_eq!(run_protected("3 drop"), Ok(None));
+        assert_eq!(
+            run_protected("drop"),
+            Err(RuntimeError::ProtectedUnderflow(0))
+        );
+        assert_eq!(run_protected("3 discard"), Ok(None));
+        assert_eq!(
+            run_protected("discard"),
+            Err(RuntimeError::ProtectedUnderflow(0))
+        );
+    }
+
+    #[test]
+    fn factorial() {
+        assert_eq!(run("0 fact"), Ok(Some(1)));
+        assert_eq!(run("5 fact"), Ok(Some(120)));
+        assert_eq!(run("20 fact"), Ok(Some(2_432_902_008_176_640_000)));
+        assert_eq!(run("21 fact"), Err(RuntimeError::Overflow(1)));
+        assert_eq!(run("-1 fact"), Err(RuntimeError::NegativeOperand(1)));
+    }
+
+    #[test]
+    fn fill() {
+        assert_eq!(stack("1 7 0 fill"), vec![1]);
+        assert_eq!(stack("1 7 3 fill"), vec![1, 7, 7, 7]);
+        assert_eq!(run("7 -1 fill"), Err(RuntimeError::IndexOutOfBounds(2)));
+
+        let limited = Slug {
+            stack_limit : Some(4),
+            ..Slug::new()
+        };
+        assert_eq!(
+            run_with(limited, "1 7 9223372036854775807 fill"),
+            Err(RuntimeError::StackLimitHit(3))
+        );
+    }
+
+    #[test]
+    fn op_times() {
+        let mut runtime = Slug {
+            op_times : Some(HashMap::new()),
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens =
+            Tokenizer::parse_text("1 2 add 3 mul 4 add").expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        let times = runtime.op_times.expect("Opperations should be timed");
+        let mut ops : Vec<Opp> = times.into_keys().collect();
+        ops.sort_by_key(|op| *op as u8);
+        assert_eq!(ops, [Opp::Add, Opp::Mul]);
+    }
+
+    #[test]
+    fn isprime() {
+        for (v, prime) in [
+            (-7, 0),
+            (0, 0),
+            (1, 0),
+            (2, 1),
+            (3, 1),
+            (9, 0),
+            (97, 1),
+            (561, 0),
+            (1_000_000_007, 1),
+            (3_215_031_751, 0),
+            (9_223_372_036_854_775_783, 1),
+            (i64::MAX, 0),
+        ] {
+            assert_eq!(run(&format!("{v} isprime")), Ok(Some(prime)), "{v}");
+        }
+    }
+
+    #[test]
+    fn goto_targets() {
+        // Jumping to `pos` pushes the index it landed on
+        let mut runtime = Slug {
+            token_limit : Some(6),
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("pos 0 goto").expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Err(RuntimeError::TokenLimitHit(1)));
+        assert_eq!(runtime.stack, vec![0, 0, 0]);
+
+        // `goto` lands back on itself, with nothing left to pop
+        assert_eq!(run("1 goto"), Err(RuntimeError::UnderRead(1)));
+        assert_eq!(stack("3 goto 99 pos"), vec![3]);
+    }
+
+    #[test]
+    fn digit_sum() {
+        assert_eq!(run("0 digitsum"), Ok(Some(0)));
+        assert_eq!(run("9875 digitsum"), Ok(Some(29)));
+        assert_eq!(run("9223372036854775807 digitsum"), Ok(Some(88)));
+        assert_eq!(run("-12 digitsum"), Err(RuntimeError::NegativeOperand(1)));
+    }
+
+    #[test]
+    fn input_str() {
+        assert_eq!(
+            run_with(Slug::with_input_str("17\n25\n"), "in in add"),
+            Ok(Some(42))
+        );
+    }
+
+    #[test]
+    fn dup_if() {
+        assert_eq!(stack("4 1 dupif"), vec![4, 4]);
+        assert_eq!(stack("4 0 dupif"), vec![4]);
+        assert_eq!(run("1 dupif"), Err(RuntimeError::UnderRead(1)));
+    }
+
+    #[test]
+    fn spill_fillback() {
+        assert_eq!(stack("1 2 3 spill fillback"), vec![1, 2, 3]);
+        assert_eq!(stack("1 2 spill 7 fillback"), vec![7, 1, 2]);
+        assert_eq!(stack("spill fillback"), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn fib() {
+        assert_eq!(run("0 fib"), Ok(Some(0)));
+        assert_eq!(run("1 fib"), Ok(Some(1)));
+        assert_eq!(run("10 fib"), Ok(Some(55)));
+        assert_eq!(run("92 fib"), Ok(Some(7_540_113_804_746_346_429)));
+        assert_eq!(run("93 fib"), Err(RuntimeError::Overflow(1)));
+        assert_eq!(run("-1 fib"), Err(RuntimeError::NegativeOperand(1)));
+    }
+
+    #[test]
+    fn assert_empty() {
+        assert_eq!(stack("assertempty 1"), vec![1]);
+        assert_eq!(stack("1 drop assertempty"), Vec::<i64>::new());
+        assert_eq!(run("1 assertempty"), Err(RuntimeError::AssertionFailed(1)));
+    }
+
+    #[test]
+    fn max_literal() {
+        let limited = || {
+            Slug {
+                max_literal : Some(100),
+                ..Slug::new()
+            }
+        };
+        assert_eq!(run_with(limited(), "100 -100 add"), Ok(Some(0)));
+        assert_eq!(
+            run_with(limited(), "1 101"),
+            Err(RuntimeError::LiteralTooLarge(1))
+        );
+        assert_eq!(
+            run_with(limited(), "-101"),
+            Err(RuntimeError::LiteralTooLarge(0))
+        );
+
+        let program = Tokenizer::parse_program("data 5 1000 end 0 dataload 1 dataload", &[])
+            .expect("Test programs should parse");
+        let mut runtime = Slug {
+            tokens : program.tokens,
+            data : program.data,
+            eof : true,
+            out : Box::new(io::sink()),
+            ..limited()
+        };
+        assert_eq!(runtime.execute(), Err(RuntimeError::LiteralTooLarge(3)));
+        assert_eq!(runtime.stack, vec![5, 1]);
+    }
+
+    #[test]
+    fn floor_pow2() {
+        assert_eq!(run("1 floorpow2"), Ok(Some(1)));
+        assert_eq!(run("64 floorpow2"), Ok(Some(64)));
+        assert_eq!(run("100 floorpow2"), Ok(Some(64)));
+        assert_eq!(run("9223372036854775807 floorpow2"), Ok(Some(1 << 62)));
+        assert_eq!(run("0 floorpow2"), Err(RuntimeError::NonPositiveOperand(1)));
+        assert_eq!(
+            run("-4 floorpow2"),
+            Err(RuntimeError::NonPositiveOperand(1))
+        );
+    }
+
+    #[test]
+    fn is_streaming() {
+        assert_eq!(stack("isstreaming"), vec![1]);
+        assert_eq!(run("isstreaming"), Ok(Some(0)));
+    }
+
+    #[test]
+    fn distinct() {
+        assert_eq!(stack("3 1 3 2 1 distinct"), vec![3, 1, 3, 2, 1, 3]);
+        assert_eq!(run("4 5 6 distinct"), Ok(Some(3)));
+        assert_eq!(run("distinct"), Ok(Some(0)));
+    }
+
+    #[test]
+    fn saturating_arith() {
+        let saturating = || {
+            Slug {
+                arith_mode : ArithMode::Saturating,
+                ..Slug::new()
+            }
+        };
+        assert_eq!(
+            run_with(saturating(), "9223372036854775807 1 add"),
+            Ok(Some(i64::MAX))
+        );
+        assert_eq!(
+            run_with(saturating(), "-9223372036854775808 1 sub"),
+            Ok(Some(i64::MIN))
+        );
+        assert_eq!(
+            run_with(saturating(), "-4611686018427387904 3 mul"),
+            Ok(Some(i64::MIN))
+        );
+        assert_eq!(run_with(saturating(), "2 3 add"), Ok(Some(5)));
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(run("len"), Ok(Some(1)));
+        assert_eq!(stack("1 2 len 4"), vec![1, 2, 4, 4]);
+    }
+
+    #[test]
+    fn exit_if() {
+        assert_eq!(run("7 1 exitif 8"), Ok(Some(7)));
+        assert_eq!(run("7 0 exitif 8"), Ok(Some(8)));
+        assert_eq!(run("1 exitif"), Err(RuntimeError::NoOut));
+    }
+
+    #[test]
+    fn byte() {
+        assert_eq!(run("4660 0 byte"), Ok(Some(0x34)));
+        assert_eq!(run("4660 1 byte"), Ok(Some(0x12)));
+        assert_eq!(run("-1 7 byte"), Ok(Some(0xFF)));
+        assert_eq!(run("4660 8 byte"), Err(RuntimeError::IndexOutOfBounds(2)));
+        assert_eq!(run("4660 -1 byte"), Err(RuntimeError::IndexOutOfBounds(2)));
+    }
+
+    #[test]
+    fn swap_halves() {
+        assert_eq!(stack("1 2 3 4 swaphalves"), vec![3, 4, 1, 2]);
+        assert_eq!(stack("1 2 3 4 5 swaphalves"), vec![4, 5, 3, 1, 2]);
+        assert_eq!(stack("1 swaphalves"), vec![1]);
+    }
+
+    #[test]
+    fn checked_arith() {
+        assert_eq!(
+            run("9223372036854775807 1 add"),
+            Err(RuntimeError::Overflow(2))
+        );
+        assert_eq!(
+            run("-9223372036854775808 1 sub"),
+            Err(RuntimeError::Overflow(2))
+        );
+        assert_eq!(
+            run("4611686018427387904 2 mul"),
+            Err(RuntimeError::Overflow(2))
+        );
+        assert_eq!(run("9223372036854775806 1 add"), Ok(Some(i64::MAX)));
+
+        let wrapping = Slug {
+            arith_mode : ArithMode::Wrapping,
+            ..Slug::new()
+        };
+        assert_eq!(
+            run_with(wrapping, "9223372036854775807 1 add"),
+            Ok(Some(i64::MIN))
+        );
+    }
+
+    #[test]
+    fn div() {
+        assert_eq!(run("5 0 div"), Err(RuntimeError::DivByZero(2)));
+        assert_eq!(stack("7 2 div"), vec![1, 3]);
+        assert_eq!(stack("-7 2 div"), vec![-1, -3]);
+        assert_eq!(
+            run("-9223372036854775808 -1 div"),
+            Err(RuntimeError::Overflow(2))
+        );
+    }
+
+    #[test]
+    fn dup() {
+        assert_eq!(stack("4 dup"), vec![4, 4]);
+        assert_eq!(run("3 dup mul"), Ok(Some(9)));
+        assert_eq!(run("dup"), Err(RuntimeError::UnderRead(0)));
+    }
+
+    #[test]
+    fn log2() {
+        assert_eq!(run("1 log2"), Ok(Some(0)));
+        assert_eq!(run("1024 log2"), Ok(Some(10)));
+        assert_eq!(run("1023 log2"), Ok(Some(9)));
+        assert_eq!(run("9223372036854775807 log2"), Ok(Some(62)));
+        assert_eq!(run("0 log2"), Err(RuntimeError::NonPositiveOperand(1)));
+        assert_eq!(run("-8 log2"), Err(RuntimeError::NonPositiveOperand(1)));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(run("3 3 eq"), Ok(Some(1)));
+        assert_eq!(run("3 4 eq"), Ok(Some(0)));
+        assert_eq!(run("2 5 lt"), Ok(Some(1)));
+        assert_eq!(run("5 2 lt"), Ok(Some(0)));
+        assert_eq!(run("5 2 gt"), Ok(Some(1)));
+        assert_eq!(run("2 2 gt"), Ok(Some(0)));
+        assert_eq!(run("eq"), Err(RuntimeError::UnderRead(0)));
+        assert_eq!(run("1 lt"), Err(RuntimeError::UnderRead(1)));
+    }
+
+    #[test]
+    fn jz_countdown() {
+        // Counts down from 3, leaving the loop through `jz` once it hits 0
+        let mut runtime = Slug {
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("3 dup 8 jz 1 sub 1 goto 42")
+            .expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        assert_eq!(runtime.stack, vec![0, 42]);
+        assert_eq!(runtime.tokens_consumed, 1 + 3 * 7 + 3 + 1);
+
+        assert_eq!(stack("1 5 jz 7"), vec![7]);
+        assert_eq!(run("0 -1 jz"), Err(RuntimeError::BreforeProgramRead));
+    }
+
+    #[test]
+    fn rotate_bits() {
+        assert_eq!(run("291 0 rol"), Ok(Some(291)));
+        assert_eq!(run("291 4 rol"), Ok(Some(0x1230)));
+        assert_eq!(run("291 68 rol"), Ok(Some(0x1230)));
+        assert_eq!(run("291 0 ror"), Ok(Some(291)));
+        assert_eq!(run("291 4 ror"), Ok(Some(0x3000_0000_0000_0012)));
+        assert_eq!(run("291 68 ror"), Ok(Some(0x3000_0000_0000_0012)));
+        assert_eq!(run("291 -4 ror"), Ok(Some(0x1230)));
+    }
+
+    #[test]
+    fn neg() {
+        assert_eq!(run("5 neg"), Ok(Some(-5)));
+        assert_eq!(run("-5 neg"), Ok(Some(5)));
+        assert_eq!(run("0 neg"), Ok(Some(0)));
+        assert_eq!(
+            run("-9223372036854775808 neg"),
+            Err(RuntimeError::Overflow(1))
+        );
+    }
+
+    #[test]
+    fn total_cell_limit() {
+        let limited = || {
+            Slug {
+                total_cell_limit : Some(5),
+                ..Slug::new()
+            }
+        };
+        assert_eq!(run_with(limited(), "1 2 3 spill 4 5"), Ok(Some(5)));
+        assert_eq!(
+            run_with(limited(), "1 2 3 spill 4 5 6"),
+            Err(RuntimeError::MemoryLimitHit(7))
+        );
+        assert_eq!(
+            run_with(limited(), "1 2 spill 9 9223372036854775807 fill"),
+            Err(RuntimeError::MemoryLimitHit(5))
+        );
+    }
+
+    #[test]
+    fn median() {
+        assert_eq!(stack("9 1 5 median"), vec![5]);
+        // The lower of the two middle values
+        assert_eq!(stack("8 2 6 4 median"), vec![4]);
+        assert_eq!(run("median"), Err(RuntimeError::UnderRead(0)));
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(run("17 5 mod"), Ok(Some(2)));
+        assert_eq!(run("-17 5 mod"), Ok(Some(-2)));
+        assert_eq!(run("17 0 mod"), Err(RuntimeError::DivByZero(2)));
+    }
+
+    #[test]
+    fn input() {
+        let reading = |input : &'static [u8]| {
+            Slug {
+                input : Some(Box::new(input)),
+                ..Slug::new()
+            }
+        };
+        assert_eq!(run_with(reading(b"  12\n-3 "), "in in add"), Ok(Some(9)));
+        assert_eq!(
+            run_with(reading(b"12 abc"), "in in"),
+            Err(RuntimeError::InputError(1))
+        );
+        assert_eq!(
+            run_with(reading(b"12"), "in in"),
+            Err(RuntimeError::InputError(1))
+        );
+    }
+
+    #[test]
+    fn already_halted() {
+        let mut runtime = Slug {
+            eof : true,
+            out : Box::new(io::sink()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("2 3 add").expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(Some(5)));
+        assert_eq!(runtime.execute(), Err(RuntimeError::AlreadyHalted));
+
+        runtime.reset();
+        assert_eq!(runtime.execute(), Ok(Some(5)));
+    }
+
+    #[test]
+    fn emit() {
+        let out = Captured::default();
+        let mut runtime = Slug {
+            out : Box::new(out.clone()),
+            ..Slug::new()
+        };
+        runtime.tokens = Tokenizer::parse_text("72 emit 105 emit 128512 emit")
+            .expect("Test programs should parse");
+        assert_eq!(runtime.execute(), Ok(None));
+        assert_eq!(String::from_utf8_lossy(&out.0.borrow()), "Hi😀");
+
+        assert_eq!(run("55296 emit"), Err(RuntimeError::BadChar(1)));
+        assert_eq!(run("-1 emit"), Err(RuntimeError::BadChar(1)));
+    }
+
+    #[test]
+    fn huge_hops() {
+        assert_eq!(
+            run("9223372036854775807 hop"),
+            Err(RuntimeError::AfterProgramRead)
+        );
+        assert_eq!(
+            run("9223372036854775807 1 hopif"),
+            Err(RuntimeError::AfterProgramRead)
+        );
+        assert_eq!(
+            run("1 9223372036854775807 hopif"),
+            Err(RuntimeError::AfterProgramRead)
+        );
+        assert_eq!(
+            run("-9223372036854775808 hop"),
+            Err(RuntimeError::BreforeProgramRead)
+        );
+        assert_eq!(
+            run("9223372036854775807 goto"),
+            Err(RuntimeError::AfterProgramRead)
+        );
+        assert_eq!(
+            run("-9223372036854775808 goto"),
+            Err(RuntimeError::BreforeProgramRead)
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let sandboxed = || {
+            Slug {
+                deterministic : true,
+                ..Slug::new()
+            }
+        };
+        assert_eq!(
+            run_with(sandboxed(), "1 now"),
+            Err(RuntimeError::NonDeterministic(1))
+        );
+        assert_eq!(run_with(sandboxed(), "2 3 add"), Ok(Some(5)));
+    }
+
+    #[test]
+    fn reset_restarts_clock() {
+        let mut runtime = Slug {
+            time_limit : Some(Duration::from_mins(1)),
+            ..Slug::new()
+        };
+        runtime.started = Instant::now()
+            .checked_sub(Duration::from_mins(2))
+            .expect("The clock should be far enough from its epoch");
+        runtime.tokens = Tokenizer::parse_text("1 now").expect("Test programs should parse");
+        runtime.eof = true;
+        runtime.out = Box::new(io::sink());
+        assert_eq!(runtime.execute(), Err(RuntimeError::TimeLimitHit(1)));
+
+        runtime.reset();
+        let ms = runtime.execute().expect("The clock should have restarted");
+        assert!(ms.is_some_and(|ms| ms < 60_000));
+    }
+
+    #[test]
+    fn custom_names_are_interned() {
+        let name = |runtime : &Slug| {
+            runtime
+                .custom_ops
+                .keys()
+                .next()
+                .expect("An opperation should be registered")
+                .as_ptr()
+        };
+        let mut a = Slug::new();
+        a.register_op("triple", |_| Ok(()));
+        let mut b = Slug::new();
+        b.register_op("triple", |_| Ok(()));
+        assert_eq!(name(&a), name(&b));
+    }
+}
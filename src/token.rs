@@ -1,21 +1,62 @@
+use std::collections::BTreeSet;
 use std::fmt::{
     self,
     Display,
     Formatter,
 };
 use std::str::FromStr;
+use std::sync::{
+    Mutex,
+    PoisonError,
+};
 
-use crate::error::ParseTextError;
+use crate::error::{
+    ParseTextError,
+    ParseTextErrorKind,
+};
 use crate::opp::Opp;
 
-#[derive(Debug, Clone, Copy, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Token {
     Value(i64),
     Opp(Opp),
+    /// An opperation registered on a runtime with `Slug::register_op`
+    Custom(&'static str),
+}
+
+impl Token {
+    /// Decodes a token from a single value, as used by `eval`. Non-negative
+    /// values are literals and a negative value `c` is the opperation whose
+    /// discriminant is `!c`, so `-1` is `add` and `-2` is `sub`.
+    #[must_use]
+    pub fn from_code(code : i64) -> Option<Self> {
+        if code >= 0 {
+            Some(Self::Value(code))
+        } else {
+            usize::try_from(!code)
+                .ok()
+                .and_then(|d| Opp::all().get(d))
+                .map(|op| Self::Opp(*op))
+        }
+    }
+}
+
+/// Gives the leaked copy of `name` for a `Token::Custom`, leaking it the
+/// first time it's seen so each name is only leaked once per process
+pub(crate) fn intern(name : &str) -> &'static str {
+    static NAMES : Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(name) = names.get(name) {
+        return name;
+    }
+    let name = Box::leak(name.to_owned().into_boxed_str());
+    names.insert(name);
+    name
 }
 
 impl FromStr for Token {
-    type Err = ();
+    type Err = ParseTextErrorKind;
 
     fn from_str(s : &str) -> Result<Self, Self::Err> {
         #[expect(
@@ -26,22 +67,47 @@ impl FromStr for Token {
             Ok(Self::Value(num))
         } else if let Ok(op) = s.parse::<Opp>() {
             Ok(Self::Opp(op))
+        } else if is_integer_literal(s) {
+            Err(ParseTextErrorKind::LiteralOutOfRange)
         } else {
-            Err(())
+            Err(ParseTextErrorKind::UnknownToken)
         }
     }
 }
 
+/// Whether a word is written as an integer, optionally signed, regardless of
+/// whether it fits in an `i64`
+fn is_integer_literal(s : &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
 impl Display for Token {
     fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
         let t = match self {
             Self::Value(i) => format!("{i}"),
             Self::Opp(i) => format!("{i}"),
+            Self::Custom(name) => (*name).to_owned(),
         };
         write!(f, "{t}")
     }
 }
 
+/// A parsed program, its executable tokens along with the values of its data
+/// sections
+#[derive(Debug, Default)]
+pub struct Program {
+    pub tokens : Vec<Token>,
+    pub data :   Vec<i64>,
+}
+
+/// The byte range of a token in the text it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start : usize,
+    pub end :   usize,
+}
+
 pub struct Tokenizer {}
 
 impl Tokenizer {
@@ -49,28 +115,353 @@ impl Tokenizer {
     ///
     /// # Errors
     /// If the inputed text is syntaxtically invalid
+    pub fn parse_text(text : &str) -> Result<Vec<Token>, ParseTextError> {
+        Self::parse_text_spanned(text).map(|(tokens, _)| tokens)
+    }
+
+    /// Tokenizes a string slice like `Self::parse_text`, also returning where
+    /// each token was in the text. The spans are parallel to the tokens.
     ///
-    /// # Panics
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid
+    pub fn parse_text_spanned(text : &str) -> Result<(Vec<Token>, Vec<Span>), ParseTextError> {
+        let mut tokens = Vec::new();
+        let mut spans = Vec::new();
+
+        for (idx, (span, word)) in Self::spanned_words(text, &[]).enumerate() {
+            let token = word.parse().map_err(|kind| {
+                ParseTextError {
+                    idx,
+                    kind,
+                }
+            })?;
+            tokens.push(token);
+            spans.push(span);
+        }
+
+        Ok((tokens, spans))
+    }
+
+    /// Tokenizes a string slice like `Self::parse_text`, but rather than
+    /// stopping at the first word that fails to parse, reports every one
     ///
-    /// This shouldn't be possible
-    pub fn parse_text(text : &str) -> Result<Vec<Token>, ParseTextError> {
-        let tokens : Vec<(usize, Result<Token, ()>)> = text
-            .split_ascii_whitespace()
-            .enumerate()
-            .map(|(idx, word)| (idx, word.parse::<Token>()))
-            .collect();
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid, with an error for each
+    /// invalid word in order
+    pub fn parse_text_all(text : &str) -> Result<Vec<Token>, Vec<ParseTextError>> {
+        let mut tokens = Vec::new();
+        let errors = Self::parse_words(text.split_ascii_whitespace().enumerate(), &mut tokens);
+
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Tokenizes `new_text` given the tokens `prev` already parsed from
+    /// `old_text`, only parsing from the first word that differs between
+    /// them. Tokens are context free, so everything before that word is
+    /// reused as is.
+    ///
+    /// Rather than stopping at the first error, every word that fails to
+    /// parse is reported and left out of the tokens. The prefix is only reused
+    /// when `prev` has a token for every word of `old_text`.
+    #[must_use]
+    pub fn reparse(
+        prev : &[Token],
+        old_text : &str,
+        new_text : &str,
+    ) -> (Vec<Token>, Vec<ParseTextError>) {
+        let old_words : Vec<&str> = old_text.split_ascii_whitespace().collect();
+        let new_words : Vec<&str> = new_text.split_ascii_whitespace().collect();
 
-        if tokens.iter().all(|(_, tok)| Result::is_ok(tok)) {
-            Ok(tokens
+        let reused = if prev.len() == old_words.len() {
+            old_words
                 .iter()
-                .map(|(_, tok)| tok.expect("Can't happen, the vec has been validated already"))
-                .collect())
-        } else if let Some((idx, _)) = tokens.iter().find(|(_, tok)| tok.is_err()) {
-            Err(ParseTextError {
-                idx : *idx
-            })
+                .zip(&new_words)
+                .take_while(|(old, new)| old == new)
+                .count()
+        } else {
+            0
+        };
+
+        let mut tokens = prev[.. reused].to_vec();
+        let errors = Self::parse_words(new_words.into_iter().enumerate().skip(reused), &mut tokens);
+
+        (tokens, errors)
+    }
+
+    /// Parses each indexed word onto the end of `tokens`, carrying on past
+    /// words that fail to parse and returning an error for each of them
+    fn parse_words<'a>(
+        words : impl Iterator<Item = (usize, &'a str)>,
+        tokens : &mut Vec<Token>,
+    ) -> Vec<ParseTextError> {
+        let mut errors = Vec::new();
+        for (idx, word) in words {
+            #[cfg(test)]
+            tests::WORDS_PARSED.set(tests::WORDS_PARSED.get() + 1);
+            match word.parse() {
+                Ok(token) => tokens.push(token),
+                Err(kind) => {
+                    errors.push(ParseTextError {
+                        idx,
+                        kind,
+                    });
+                },
+            }
+        }
+        errors
+    }
+
+    /// Parses a program which may contain data sections, `data 1 2 3 end`.
+    /// The values of every data section are collected in order and aren't
+    /// executable tokens, so they don't affect token indices.
+    ///
+    /// Words are split on whitespace and on any of the `separators`, so with
+    /// `&[',']` the text `2, 3, add` is three tokens.
+    ///
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid or a data section is
+    /// malformed, with an error for each problem in order
+    pub fn parse_program(
+        text : &str,
+        separators : &[char],
+    ) -> Result<Program, Vec<ParseTextError>> {
+        Self::parse_program_custom(text, separators, &[])
+    }
+
+    /// Parses a program like `Self::parse_program`, also accepting the names
+    /// of custom opperations
+    ///
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid or a data section is
+    /// malformed, with an error for each problem in order
+    pub fn parse_program_custom(
+        text : &str,
+        separators : &[char],
+        custom : &[&'static str],
+    ) -> Result<Program, Vec<ParseTextError>> {
+        Self::parse_program_inner(text, separators, custom).map(|(program, _)| program)
+    }
+
+    /// Parses a program like `Self::parse_program`, also returning where each
+    /// executable token was in the text. The spans are parallel to the tokens.
+    ///
+    /// # Errors
+    /// If the inputed text is syntaxtically invalid or a data section is
+    /// malformed, with an error for each problem in order
+    pub fn parse_program_spanned(
+        text : &str,
+        separators : &[char],
+    ) -> Result<(Program, Vec<Span>), Vec<ParseTextError>> {
+        Self::parse_program_inner(text, separators, &[])
+    }
+
+    fn parse_program_inner(
+        text : &str,
+        separators : &[char],
+        custom : &[&'static str],
+    ) -> Result<(Program, Vec<Span>), Vec<ParseTextError>> {
+        let mut program = Program::default();
+        let mut spans = Vec::new();
+        let mut errors = Vec::new();
+        let mut words = Self::spanned_words(text, separators).enumerate();
+
+        while let Some((idx, (span, word))) = words.next() {
+            if word != "data" {
+                let token = word.parse().or_else(|kind| {
+                    custom
+                        .iter()
+                        .find(|name| **name == word)
+                        .map(|name| Token::Custom(name))
+                        .ok_or(ParseTextError {
+                            idx,
+                            kind,
+                        })
+                });
+                match token {
+                    Ok(token) => {
+                        program.tokens.push(token);
+                        spans.push(span);
+                    },
+                    Err(err) => errors.push(err),
+                }
+                continue;
+            }
+
+            loop {
+                match words.next() {
+                    Some((_, (_, "end"))) => break,
+                    Some((idx, (_, value))) => {
+                        match value.parse() {
+                            Ok(value) => program.data.push(value),
+                            Err(_) => {
+                                errors.push(ParseTextError {
+                                    idx,
+                                    kind : ParseTextErrorKind::InvalidData,
+                                });
+                            },
+                        }
+                    },
+                    None => {
+                        errors.push(ParseTextError {
+                            idx,
+                            kind : ParseTextErrorKind::UnterminatedData,
+                        });
+                        break;
+                    },
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok((program, spans))
         } else {
-            unreachable!();
+            Err(errors)
+        }
+    }
+
+    /// Splits a leading `#!` line, as used to run a program as a script,
+    /// from the rest of the text. The shebang includes its line ending.
+    #[must_use]
+    pub fn split_shebang(text : &str) -> (Option<&str>, &str) {
+        if !text.starts_with("#!") {
+            return (None, text);
         }
+
+        let end = text.find('\n').map_or(text.len(), |idx| idx + 1);
+        let (shebang, rest) = text.split_at(end);
+        (Some(shebang), rest)
+    }
+
+    /// Splits text into words on whitespace and any of the `separators`,
+    /// along with where each word is
+    fn spanned_words<'a>(
+        text : &'a str,
+        separators : &'a [char],
+    ) -> impl Iterator<Item = (Span, &'a str)> {
+        text.split(|c : char| c.is_ascii_whitespace() || separators.contains(&c))
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                // Every word is a slice of `text`
+                let start = word.as_ptr().addr() - text.as_ptr().addr();
+                (
+                    Span {
+                        start,
+                        end : start + word.len(),
+                    },
+                    word,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    thread_local! {
+        /// How many words `Tokenizer::parse_words` has tokenized on this thread
+        pub static WORDS_PARSED : Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Formats tokens back into words, for comparing
+    fn texts(tokens : &[Token]) -> Vec<String> {
+        tokens.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn separators() {
+        let program = Tokenizer::parse_program("2, 3,add;", &[',', ';']).expect("Should parse");
+        assert_eq!(texts(&program.tokens), ["2", "3", "add"]);
+
+        let errors =
+            Tokenizer::parse_program("2, 3, add", &[]).expect_err("Commas aren't separators");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].idx, 0);
+        assert_eq!(errors[0].kind, ParseTextErrorKind::UnknownToken);
+    }
+
+    #[test]
+    fn literal_out_of_range() {
+        for text in ["1 9223372036854775808", "1 -9223372036854775809"] {
+            let err = Tokenizer::parse_text(text).expect_err("Literal doesn't fit an i64");
+            assert_eq!(err.idx, 1);
+            assert_eq!(err.kind, ParseTextErrorKind::LiteralOutOfRange);
+        }
+        let tokens = Tokenizer::parse_text("-9223372036854775808").expect("Should parse");
+        assert_eq!(texts(&tokens), ["-9223372036854775808"]);
+    }
+
+    #[test]
+    fn spans() {
+        let text = "123  -45\n\tadd 6";
+        let (tokens, spans) = Tokenizer::parse_text_spanned(text).expect("Should parse");
+        let words : Vec<&str> = spans
+            .iter()
+            .map(|span| &text[span.start .. span.end])
+            .collect();
+        assert_eq!(words, ["123", "-45", "add", "6"]);
+        assert_eq!(texts(&tokens), words);
+        assert_eq!(
+            spans[1],
+            Span {
+                start : 5,
+                end :   8,
+            }
+        );
+    }
+
+    #[test]
+    fn reparse() {
+        // Reparses `new_text` after `old_text`, giving the result and how many
+        // words were tokenized again
+        let reparse = |old_text : &str, new_text : &str| {
+            let prev = Tokenizer::parse_text(old_text).expect("Should parse");
+            WORDS_PARSED.set(0);
+            let reparsed = Tokenizer::reparse(&prev, old_text, new_text);
+            (reparsed, WORDS_PARSED.get())
+        };
+
+        // Only the edited last word is tokenized again
+        let ((tokens, errors), parsed) = reparse("1 2 add 3 mul", "1 2 add 3 sub");
+        assert_eq!(parsed, 1);
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens,
+            Tokenizer::parse_text("1 2 add 3 sub").expect("Should parse")
+        );
+
+        let ((tokens, errors), parsed) = reparse("1 2 add", "1 2 sub foo");
+        assert_eq!(parsed, 2);
+        assert_eq!(texts(&tokens), ["1", "2", "sub"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].idx, 3);
+
+        let errors = Tokenizer::parse_text_all("foo 1 bar").expect_err("Two words are invalid");
+        let idxs : Vec<usize> = errors.iter().map(|err| err.idx).collect();
+        assert_eq!(idxs, [0, 2]);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let errors = Tokenizer::parse_program("foo 1 bar 2 baz add data 3 x end data 4", &[])
+            .expect_err("The program has several bad words");
+        let found : Vec<(usize, ParseTextErrorKind)> =
+            errors.iter().map(|err| (err.idx, err.kind)).collect();
+        assert_eq!(
+            found,
+            [
+                (0, ParseTextErrorKind::UnknownToken),
+                (2, ParseTextErrorKind::UnknownToken),
+                (4, ParseTextErrorKind::UnknownToken),
+                (8, ParseTextErrorKind::InvalidData),
+                (10, ParseTextErrorKind::UnterminatedData),
+            ]
+        );
     }
 }